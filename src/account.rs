@@ -0,0 +1,99 @@
+use crate::position::{Position, Transaction};
+use crate::lots::LotPolicy;
+use crate::utils::pooled_connection;
+use std::collections::HashMap;
+
+lazy_static::lazy_static! {
+    pub static ref ACCOUNT_PREFIX: String = String::from("ACCOUNT");
+}
+
+pub(crate) fn json_error(e: serde_json::Error) -> redis::RedisError {
+    redis::RedisError::from((redis::ErrorKind::TypeError, "Invalid JSON", e.to_string()))
+}
+
+// =============================================================================
+//                          Account Namespace on Redis
+// =============================================================================
+// ACCOUNT:<id>:POSITION      -> JSON snapshot of the `Position`
+// ACCOUNT:<id>:TRANSACTIONS  -> Stream of `Transaction`s, the audit log
+
+#[derive(Clone, Debug)]
+pub struct Account {
+    pub id: String,
+}
+
+impl Account {
+    pub fn new(id: &str) -> Self {
+        Self { id: id.to_string() }
+    }
+
+    pub fn key(&self) -> String {
+        format!("{}:{}", *ACCOUNT_PREFIX, self.id)
+    }
+
+    pub fn position_key(&self) -> String {
+        format!("{}:POSITION", self.key())
+    }
+
+    pub fn transactions_key(&self) -> String {
+        format!("{}:TRANSACTIONS", self.key())
+    }
+
+    pub fn save_position(&self, position: &Position) -> redis::RedisResult<()> {
+        let mut connection = pooled_connection()?;
+        let value = serde_json::to_string(position).map_err(json_error)?;
+        redis::Cmd::set(self.position_key(), value).query(&mut *connection)
+    }
+
+    pub fn load_position(&self) -> redis::RedisResult<Option<Position>> {
+        let mut connection = pooled_connection()?;
+        let value: Option<String> = redis::Cmd::get(self.position_key()).query(&mut *connection)?;
+        value
+            .map(|v| serde_json::from_str(&v).map_err(json_error))
+            .transpose()
+    }
+
+    // Appends to the audit log and returns the stream id of the entry.
+    pub fn append_transaction(&self, transaction: &Transaction) -> redis::RedisResult<String> {
+        let mut connection = pooled_connection()?;
+        let value = serde_json::to_string(transaction).map_err(json_error)?;
+
+        let mut cmd = redis::Cmd::new();
+        cmd.arg("XADD")
+            .arg(self.transactions_key())
+            .arg("*")
+            .arg("transaction")
+            .arg(value);
+        cmd.query(&mut *connection)
+    }
+
+    pub fn transactions(&self) -> redis::RedisResult<Vec<Transaction>> {
+        let mut connection = pooled_connection()?;
+
+        let mut cmd = redis::Cmd::new();
+        cmd.arg("XRANGE").arg(self.transactions_key()).arg("-").arg("+");
+        let entries: Vec<(String, HashMap<String, String>)> = cmd.query(&mut *connection)?;
+
+        entries
+            .into_iter()
+            .filter_map(|(_, fields)| fields.get("transaction").cloned())
+            .map(|v| serde_json::from_str(&v).map_err(json_error))
+            .collect()
+    }
+
+    // Rebuilds the `Position` from the audit log.
+    pub fn replay_position(&self, policy: LotPolicy) -> redis::RedisResult<Position> {
+        let mut position = Position::with_lot_policy(policy);
+        self.transactions()?
+            .into_iter()
+            .for_each(|t| position.add_transaction(t));
+        Ok(position)
+    }
+
+    // Logs the transaction, applies it to the position and saves the result.
+    pub fn record_transaction(&self, position: &mut Position, transaction: Transaction) -> redis::RedisResult<()> {
+        self.append_transaction(&transaction)?;
+        position.add_transaction(transaction);
+        self.save_position(position)
+    }
+}
@@ -0,0 +1,285 @@
+use crate::scrip::Scrip;
+use crate::redis_utils::RedisScrip;
+use crate::orders::Order;
+use crate::position::{Position, Transaction};
+use crate::risk::RiskGate;
+use crate::live_candle::Candle;
+use crate::error::Error;
+use chrono::prelude::*;
+use chrono::Duration;
+
+// =============================================================================
+//                              Execution Algorithms
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub enum ExecutionAlgo {
+    // Equal slices spread evenly over the duration.
+    Twap { slices: u32, duration: Duration },
+    // Slices sized by the volume profile (see `volume_profile`), spread evenly
+    // over the duration.
+    Vwap { profile: Vec<f64>, duration: Duration },
+    // Only `visible_quantity` is sent at a time. The next slice is released
+    // once the previous one is filled.
+    Iceberg { visible_quantity: u32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct ChildOrder {
+    pub due: DateTime<Local>,
+    pub order: Order,
+    pub fill: Option<Transaction>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AlgoExecution {
+    pub parent: Order,
+    pub algo: ExecutionAlgo,
+    // `ltp` when the parent order was received.
+    pub arrival_price: f64,
+    pub children: Vec<ChildOrder>,
+}
+
+impl AlgoExecution {
+    pub fn new(parent: Order, algo: ExecutionAlgo, start: DateTime<Local>) -> Self {
+        let arrival_price = parent.scrip.updated_ticker().ltp;
+        let children = Self::schedule(&parent, &algo, start);
+        Self {
+            parent,
+            algo,
+            arrival_price,
+            children,
+        }
+    }
+
+    fn schedule(parent: &Order, algo: &ExecutionAlgo, start: DateTime<Local>) -> Vec<ChildOrder> {
+        let (weights, interval) = match algo {
+            ExecutionAlgo::Twap { slices, duration } => {
+                let slices = (*slices).max(1);
+                (vec![1.0; slices as usize], *duration / (slices as i32))
+            }
+            ExecutionAlgo::Vwap { profile, duration } => {
+                let slices = profile.len().max(1);
+                (profile.clone(), *duration / (slices as i32))
+            }
+            ExecutionAlgo::Iceberg { visible_quantity } => {
+                let visible = (*visible_quantity).max(1) as i32;
+                let total = parent.quantity.abs();
+                let slices = (total + visible - 1) / visible;
+                let mut weights = vec![visible as f64; (slices - 1).max(0) as usize];
+                weights.push((total - visible * (slices - 1)) as f64);
+                (weights, Duration::zero())
+            }
+        };
+
+        split_quantity(parent.quantity, &weights)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, q)| *q != 0)
+            .map(|(i, quantity)| ChildOrder {
+                due: start + interval * (i as i32),
+                order: Order::new(parent.scrip.clone(), quantity, parent.order_type.clone()),
+                fill: None,
+            })
+            .collect()
+    }
+
+    // Executes the pending child orders that are due at `now`, each checked
+    // against `gate` with the fills so far added to `position`. Iceberg orders
+    // only release one slice per call. Execution stops at the first failed
+    // child order, which stays pending to be retried on the next call.
+    pub fn execute_due(&mut self, now: DateTime<Local>, gate: &RiskGate, position: &Position) -> Result<Vec<Transaction>, Error> {
+        let single_slice = matches!(self.algo, ExecutionAlgo::Iceberg { .. });
+        let mut transactions: Vec<Transaction> = Vec::new();
+        let mut position = position.clone();
+
+        for child in self.children.iter_mut().filter(|x| x.fill.is_none() && x.due <= now) {
+            let transaction = child.order.execute(gate, &position)?;
+            position.add_transaction(transaction.clone());
+            child.fill = Some(transaction.clone());
+            transactions.push(transaction);
+
+            if single_slice {
+                break;
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    // Blocks the current thread until every child order is executed.
+    pub fn run(&mut self, gate: &RiskGate, position: &Position) -> Result<Vec<Transaction>, Error> {
+        let mut transactions: Vec<Transaction> = Vec::new();
+        let mut position = position.clone();
+        while let Some(next_due) = self.next_due() {
+            let wait = next_due - Local::now();
+            if let Ok(wait) = wait.to_std() {
+                std::thread::sleep(wait);
+            }
+            let filled = self.execute_due(Local::now(), gate, &position)?;
+            filled.iter().for_each(|t| position.add_transaction(t.clone()));
+            transactions.extend(filled);
+        }
+        Ok(transactions)
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Local>> {
+        self.children.iter().filter(|x| x.fill.is_none()).map(|x| x.due).min()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.children.iter().all(|x| x.fill.is_some())
+    }
+
+    pub fn filled_quantity(&self) -> i32 {
+        self.fills().map(|x| x.quantity).sum()
+    }
+
+    pub fn remaining_quantity(&self) -> i32 {
+        self.parent.quantity - self.filled_quantity()
+    }
+
+    // Fraction of the parent quantity that has been filled.
+    pub fn progress(&self) -> f64 {
+        match self.parent.quantity {
+            0 => 1.0,
+            q => self.filled_quantity() as f64 / q as f64,
+        }
+    }
+
+    pub fn avg_fill_price(&self) -> Option<f64> {
+        let filled = self.filled_quantity();
+        if filled == 0 {
+            return None;
+        }
+        let amount = self.fills().fold(0.0, |x, y| x + (y.quantity as f64) * y.avg_price);
+        Some(amount / filled as f64)
+    }
+
+    // Slippage per unit against the arrival price. Positive values are a cost
+    // irrespective of the side of the order.
+    pub fn slippage(&self) -> Option<f64> {
+        let side = self.parent.quantity.signum() as f64;
+        self.avg_fill_price().map(|p| (p - self.arrival_price) * side)
+    }
+
+    pub fn slippage_bps(&self) -> Option<f64> {
+        self.slippage().map(|s| s / self.arrival_price * 10000.0)
+    }
+
+    fn fills(&self) -> impl Iterator<Item = &Transaction> {
+        self.children.iter().filter_map(|x| x.fill.as_ref())
+    }
+}
+
+// Splits `quantity` in proportion to `weights`, handing out the rounding
+// residue by the largest remainder so that the slices add up to `quantity`.
+// Negative and non-finite weights count as zero.
+pub fn split_quantity(quantity: i32, weights: &[f64]) -> Vec<i32> {
+    let weights: Vec<f64> = weights
+        .iter()
+        .map(|x| if x.is_finite() && *x > 0.0 { *x } else { 0.0 })
+        .collect();
+    let total_weight: f64 = weights.iter().sum();
+    let total = quantity.abs();
+    if weights.is_empty() || total_weight <= 0.0 {
+        return vec![quantity];
+    }
+
+    let exact: Vec<f64> = weights
+        .iter()
+        .map(|w| (total as f64) * w / total_weight)
+        .collect();
+    let mut slices: Vec<i32> = exact.iter().map(|x| x.floor() as i32).collect();
+
+    let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+    by_remainder.sort_by(|x, y| {
+        let rx = exact[*x] - exact[*x].floor();
+        let ry = exact[*y] - exact[*y].floor();
+        ry.total_cmp(&rx)
+    });
+    let residue = total - slices.iter().sum::<i32>();
+    by_remainder.iter().take(residue as usize).for_each(|i| slices[*i] += 1);
+
+    slices.into_iter().map(|x| x * quantity.signum()).collect()
+}
+
+// Intraday volume profile from historical candles, bucketed into `slices`
+// equal intervals between `start` and `end` (time of day in UTC, the same as
+// the candle timestamps). Candles outside the window are ignored.
+pub fn volume_profile(candles: &[Candle], slices: usize, start: NaiveTime, end: NaiveTime) -> Vec<f64> {
+    let mut profile = vec![0.0; slices];
+    let window = (end - start).num_seconds();
+    if slices == 0 || window <= 0 {
+        return profile;
+    }
+
+    candles.iter().for_each(|x| {
+        let offset = (x.timestamp.time() - start).num_seconds();
+        if offset >= 0 && offset < window {
+            let bucket = (offset * slices as i64 / window) as usize;
+            profile[bucket] += x.volume as f64;
+        }
+    });
+
+    let total: f64 = profile.iter().sum();
+    if total > 0.0 {
+        profile.iter_mut().for_each(|x| *x /= total);
+    }
+    profile
+}
+
+pub fn historical_volume_profile(scrip: &Scrip, slices: usize, start: NaiveTime, end: NaiveTime) -> Vec<f64> {
+    let candles: Vec<Candle> = scrip
+        .candle_ts()
+        .into_iter()
+        .filter_map(|ts| scrip.candle_from_timestamp(ts))
+        .collect();
+    volume_profile(&candles, slices, start, end)
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::algo::*;
+    use chrono::{Duration, Local};
+
+    fn test_order(quantity: i32) -> Order {
+        Order::new(Scrip::Stock(StockScrip::new("TEST", "NSE", "C")), quantity, OrderType::MarketOrder)
+    }
+
+    #[test]
+    fn split_preserves_quantity() {
+        assert_eq!(split_quantity(10, &[1.0, 1.0, 1.0]), vec![4, 3, 3]);
+        assert_eq!(split_quantity(-10, &[0.2, 0.5, 0.3]), vec![-2, -5, -3]);
+        assert_eq!(split_quantity(10, &[f64::NAN, 1.0, 1.0]), vec![0, 5, 5]);
+    }
+
+    #[test]
+    fn twap_schedule() {
+        let start = Local::now();
+        let algo = ExecutionAlgo::Twap { slices: 4, duration: Duration::minutes(20) };
+        let execution = AlgoExecution::new(test_order(12), algo, start);
+        let quantities: Vec<i32> = execution.children.iter().map(|x| x.order.quantity).collect();
+        assert_eq!(quantities, vec![3, 3, 3, 3]);
+        assert_eq!(execution.children[3].due, start + Duration::minutes(15));
+    }
+
+    #[test]
+    fn iceberg_progress() {
+        let start = Local::now();
+        let algo = ExecutionAlgo::Iceberg { visible_quantity: 5 };
+        let mut execution = AlgoExecution::new(test_order(12), algo, start);
+        assert_eq!(execution.children.len(), 3);
+
+        execution.execute_due(start, &RiskGate::new(), &Default::default()).unwrap();
+        assert_eq!(execution.filled_quantity(), 5);
+        assert_eq!(execution.remaining_quantity(), 7);
+        // Every slice of 5 fills at the best ask of 401.15 against ltp 400.23
+        assert!((execution.slippage().unwrap() - 0.92).abs() < 1e-9);
+    }
+}
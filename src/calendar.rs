@@ -0,0 +1,213 @@
+use crate::scrip::Exchange;
+use crate::session::{PhaseWindow, SessionPhase};
+use crate::info::{format_time, parse_exchange};
+use crate::error::MetadataError;
+use crate::provider::{csv_items, json_items, read_file, FileFormat, MetadataProvider};
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+// =============================================================================
+//                              Trading Calendar
+// =============================================================================
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+// Longest run of closed days searched for the next or previous trading day.
+const MAX_CLOSED_DAYS: i64 = 366;
+
+// A session on a day that is otherwise closed or has different hours, such as
+// Muhurat trading on Diwali.
+#[derive(Clone, Debug)]
+pub struct SpecialSession {
+    pub date: NaiveDate,
+    pub description: String,
+    pub phases: Vec<PhaseWindow>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TradingCalendar {
+    pub exchange: Exchange,
+    pub holidays: BTreeMap<NaiveDate, String>,
+    pub special_sessions: BTreeMap<NaiveDate, SpecialSession>,
+}
+
+impl TradingCalendar {
+    // Only weekends are closed.
+    pub fn new(exchange: Exchange) -> Self {
+        Self {
+            exchange,
+            holidays: BTreeMap::new(),
+            special_sessions: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_holiday(&mut self, date: NaiveDate, description: &str) {
+        self.holidays.insert(date, description.to_string());
+    }
+
+    pub fn add_special_session(&mut self, session: SpecialSession) {
+        self.special_sessions.insert(session.date, session);
+    }
+
+    // Items with `date` (YYYY-MM-DD), `description` and an optional `exchange`.
+    // Items with `sessionStart` and `sessionEnd` (HHMM) are special sessions,
+    // everything else is a holiday. Items for other exchanges are skipped.
+    pub fn from_items(exchange: Exchange, items: &[HashMap<String, AttributeValue>]) -> Result<Self, MetadataError> {
+        let mut calendar = Self::new(exchange);
+        for item in items.iter() {
+            if let Ok(exch) = string_attribute(item, "exchange") {
+                if parse_exchange(exch)? != exchange {
+                    continue;
+                }
+            }
+
+            let date = string_attribute(item, "date")?;
+            let date = NaiveDate::parse_from_str(date, DATE_FORMAT)
+                .map_err(|_| MetadataError::Malformed(String::from("date"), format!("expected YYYY-MM-DD, found {}", date)))?;
+            let description = string_attribute(item, "description").map(|x| x.to_string()).unwrap_or_default();
+
+            match (string_attribute(item, "sessionStart"), string_attribute(item, "sessionEnd")) {
+                (Ok(start), Ok(end)) => calendar.add_special_session(SpecialSession {
+                    date,
+                    description,
+                    phases: vec![PhaseWindow {
+                        phase: SessionPhase::Continuous,
+                        start: format_time(start)?,
+                        end: format_time(end)?,
+                    }],
+                }),
+                _ => calendar.add_holiday(date, &description),
+            }
+        }
+        Ok(calendar)
+    }
+
+    pub fn from_json(exchange: Exchange, json: &str) -> Result<Self, MetadataError> {
+        Self::from_items(exchange, &json_items(json)?)
+    }
+
+    pub fn from_csv(exchange: Exchange, csv: &str) -> Result<Self, MetadataError> {
+        Self::from_items(exchange, &csv_items(csv))
+    }
+
+    pub fn from_file(exchange: Exchange, path: &Path, format: FileFormat) -> Result<Self, MetadataError> {
+        let contents = read_file(path)?;
+        match format {
+            FileFormat::Json => Self::from_json(exchange, &contents),
+            FileFormat::Csv => Self::from_csv(exchange, &contents),
+        }
+    }
+
+    pub fn from_provider(exchange: Exchange, provider: &dyn MetadataProvider) -> Result<Self, MetadataError> {
+        provider.calendar(exchange)
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.contains_key(&date)
+    }
+
+    pub fn special_session(&self, date: NaiveDate) -> Option<&SpecialSession> {
+        self.special_sessions.get(&date)
+    }
+
+    // Special sessions are trading days even on weekends and holidays.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        if self.special_sessions.contains_key(&date) {
+            return true;
+        }
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.is_holiday(date)
+    }
+
+    // First trading day strictly after `date`, within `MAX_CLOSED_DAYS`.
+    pub fn next_trading_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        (1..=MAX_CLOSED_DAYS)
+            .map(|x| date + Duration::days(x))
+            .find(|x| self.is_trading_day(*x))
+    }
+
+    // Last trading day strictly before `date`, within `MAX_CLOSED_DAYS`.
+    pub fn previous_trading_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        (1..=MAX_CLOSED_DAYS)
+            .map(|x| date - Duration::days(x))
+            .find(|x| self.is_trading_day(*x))
+    }
+
+    // Trading days after `start` up to and including `end`.
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> usize {
+        (1..)
+            .map(|x| start + Duration::days(x))
+            .take_while(|x| *x <= end)
+            .filter(|x| self.is_trading_day(*x))
+            .count()
+    }
+}
+
+fn string_attribute<'a>(item: &'a HashMap<String, AttributeValue>, key: &str) -> Result<&'a String, MetadataError> {
+    match item.get(key) {
+        Some(AttributeValue::S(value)) => Ok(value),
+        Some(_) => Err(MetadataError::Malformed(key.to_string(), String::from("expected a string"))),
+        None => Err(MetadataError::Malformed(key.to_string(), String::from("missing"))),
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Exchange;
+    use crate::calendar::*;
+
+    const HOLIDAYS: &str = "exchange,date,description,sessionStart,sessionEnd
+        NSE,2022-10-05,Dussehra,,
+        NSE,2022-10-24,Diwali Muhurat,1815,1915
+        nse,2022-10-26,Diwali Balipratipada,,
+        MCX,2022-10-26,Diwali Balipratipada,,
+        ,2022-11-08,Gurunanak Jayanti,,";
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn holidays() {
+        let nse = TradingCalendar::from_csv(Exchange::NSE, HOLIDAYS).unwrap();
+        assert_eq!(nse.holidays.len(), 3);
+        assert!(!nse.is_trading_day(date(2022, 10, 5)));
+        assert!(nse.is_trading_day(date(2022, 10, 24)));
+        assert_eq!(nse.next_trading_day(date(2022, 10, 25)), Some(date(2022, 10, 27)));
+        assert_eq!(nse.previous_trading_day(date(2022, 10, 10)), Some(date(2022, 10, 7)));
+        // 2022-10-03 to 2022-10-10, less Dussehra and the weekend.
+        assert_eq!(nse.trading_days_between(date(2022, 10, 3), date(2022, 10, 10)), 4);
+    }
+
+    #[test]
+    fn muhurat_session() {
+        let nse = TradingCalendar::from_csv(Exchange::NSE, HOLIDAYS).unwrap();
+        let muhurat = nse.special_session(date(2022, 10, 24)).unwrap();
+        assert_eq!(muhurat.phases[0].start, NaiveTime::from_hms_opt(18, 15, 0).unwrap());
+        assert!(nse.special_session(date(2022, 10, 26)).is_none());
+    }
+
+    #[test]
+    fn closed_calendar() {
+        let mut closed = TradingCalendar::new(Exchange::MCX);
+        (0..400).for_each(|x| closed.add_holiday(date(2022, 1, 1) + Duration::days(x), "Closed"));
+        assert_eq!(closed.next_trading_day(date(2022, 1, 1)), None);
+        assert_eq!(closed.previous_trading_day(date(2023, 1, 31)), None);
+    }
+
+    #[test]
+    fn from_provider() {
+        use crate::provider::InMemoryProvider;
+        let provider = InMemoryProvider::new();
+        provider.insert_calendar(TradingCalendar::from_csv(Exchange::NSE, HOLIDAYS).unwrap());
+
+        let nse = TradingCalendar::from_provider(Exchange::NSE, &provider).unwrap();
+        assert!(!nse.is_trading_day(date(2022, 10, 26)));
+        assert!(matches!(TradingCalendar::from_provider(Exchange::MCX, &provider), Err(MetadataError::NotFound(_))));
+    }
+}
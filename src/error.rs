@@ -8,4 +8,46 @@ pub enum Error {
     EmptyDepth,
     #[error("Market Depth falls short of {0} quantity to satisfy order.")]
     InsufficientDepth(i32),
+    #[error("Basket leg {0} failed: {1}")]
+    BasketLegFailed(usize, Box<Error>),
+    // The leg that could not be squared off, why, and the leg failure that
+    // caused the unwind.
+    #[error("Unable to unwind basket leg {0}: {1}, after {2}")]
+    UnwindFailed(usize, Box<Error>, Box<Error>),
+    #[error("Order value {0} exceeds the limit of {1}")]
+    MaxOrderValueExceeded(f64, f64),
+    #[error("Order quantity {0} exceeds the per scrip limit of {1}")]
+    MaxQuantityExceeded(u32, u32),
+    #[error("Resulting position of {0} exceeds the open position limit of {1}")]
+    MaxPositionExceeded(i32, u32),
+    #[error("Order quantity {0} exceeds the freeze quantity of {1}")]
+    FreezeQuantityExceeded(u32, u32),
+    #[error("Order quantity {0} is not a multiple of the lot size {1}")]
+    NotLotMultiple(u32, u32),
+    #[error("Order price {0} is not a multiple of the tick size {1}")]
+    OffTick(f64, f64),
+    #[error("Order price {0} is outside the price band {1} - {2}")]
+    OutsidePriceBand(f64, f64, f64),
+    #[error("Running P&L {0} breaches the daily loss limit of {1}")]
+    DailyLossLimitBreached(f64, f64),
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+    #[error("Unable to read tickers: {0}")]
+    Redis(String),
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(e: redis::RedisError) -> Self {
+        Error::Redis(e.to_string())
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MetadataError {
+    #[error("No metadata found for {0}")]
+    NotFound(String),
+    #[error("Malformed attribute `{0}`: {1}")]
+    Malformed(String, String),
+    #[error("Unable to fetch metadata: {0}")]
+    Transport(String),
 }
@@ -0,0 +1,230 @@
+use crate::scrip::Exchange;
+use crate::options::OptionScrip;
+use crate::calendar::TradingCalendar;
+use crate::session::MarketSession;
+use crate::pricing;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::HashMap;
+
+// =============================================================================
+//                               Expiry Rules
+// =============================================================================
+
+// Expiry weekdays for an underlying. Monthly contracts expire on the last
+// `monthly` weekday of the month, weekly ones on every `weekly` weekday.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExpiryRules {
+    pub weekly: Option<Weekday>,
+    pub monthly: Weekday,
+}
+
+impl ExpiryRules {
+    pub fn new(weekly: Option<Weekday>, monthly: Weekday) -> Self {
+        Self { weekly, monthly }
+    }
+
+    // NSE expires on Thursdays and BSE on Fridays. MCX has no weekly contracts.
+    pub fn for_exchange(exchange: Exchange) -> Self {
+        match exchange {
+            Exchange::NSE => Self::new(Some(Weekday::Thu), Weekday::Thu),
+            Exchange::BSE => Self::new(Some(Weekday::Fri), Weekday::Fri),
+            Exchange::MCX => Self::new(None, Weekday::Thu),
+        }
+    }
+}
+
+fn last_weekday_of_month(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let (next_year, next_month) = match month {
+        12 => (year + 1, 1),
+        _ => (year, month + 1),
+    };
+    let mut date = NaiveDate::from_ymd_opt(next_year, next_month, 1).unwrap() - Duration::days(1);
+    while date.weekday() != weekday {
+        date -= Duration::days(1);
+    }
+    date
+}
+
+// =============================================================================
+//                              Expiry Calendar
+// =============================================================================
+
+// Expiries falling on a holiday move to the previous trading day.
+#[derive(Clone, Debug)]
+pub struct ExpiryCalendar {
+    pub calendar: TradingCalendar,
+    pub default_rules: ExpiryRules,
+    pub rules: HashMap<String, ExpiryRules>,
+}
+
+impl ExpiryCalendar {
+    pub fn new(calendar: TradingCalendar) -> Self {
+        let default_rules = ExpiryRules::for_exchange(calendar.exchange);
+        Self {
+            calendar,
+            default_rules,
+            rules: HashMap::new(),
+        }
+    }
+
+    // Overrides the exchange default for one underlying.
+    pub fn with_rules(mut self, underlying: &str, rules: ExpiryRules) -> Self {
+        self.rules.insert(underlying.to_string(), rules);
+        self
+    }
+
+    pub fn rules_for(&self, underlying: &str) -> ExpiryRules {
+        self.rules.get(underlying).copied().unwrap_or(self.default_rules)
+    }
+
+    fn adjust(&self, date: NaiveDate) -> NaiveDate {
+        match self.calendar.is_trading_day(date) {
+            true => date,
+            false => self.calendar.previous_trading_day(date).unwrap_or(date),
+        }
+    }
+
+    pub fn monthly_expiry(&self, underlying: &str, year: i32, month: u32) -> NaiveDate {
+        let rules = self.rules_for(underlying);
+        self.adjust(last_weekday_of_month(year, month, rules.monthly))
+    }
+
+    // Monthly expiry on or after `date`.
+    pub fn current_monthly(&self, underlying: &str, date: NaiveDate) -> NaiveDate {
+        let expiry = self.monthly_expiry(underlying, date.year(), date.month());
+        match expiry >= date {
+            true => expiry,
+            false => self.month_after(underlying, date.year(), date.month()),
+        }
+    }
+
+    // Monthly expiry after the current one.
+    pub fn next_monthly(&self, underlying: &str, date: NaiveDate) -> NaiveDate {
+        let current = self.current_monthly(underlying, date);
+        self.month_after(underlying, current.year(), current.month())
+    }
+
+    fn month_after(&self, underlying: &str, year: i32, month: u32) -> NaiveDate {
+        match month {
+            12 => self.monthly_expiry(underlying, year + 1, 1),
+            _ => self.monthly_expiry(underlying, year, month + 1),
+        }
+    }
+
+    // All weekly and monthly expiries from `start` to `end`, both inclusive.
+    pub fn expiries_between(&self, underlying: &str, start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+        let rules = self.rules_for(underlying);
+
+        // Raw dates up to a week past `end` can still be adjusted into range.
+        let mut expiries: Vec<NaiveDate> = (0..)
+            .map(|x| start + Duration::days(x))
+            .take_while(|x| *x <= end + Duration::days(7))
+            .filter(|x| {
+                Some(x.weekday()) == rules.weekly
+                    || *x == last_weekday_of_month(x.year(), x.month(), rules.monthly)
+            })
+            .map(|x| self.adjust(x))
+            .filter(|x| start <= *x && *x <= end)
+            .collect();
+        expiries.dedup();
+        expiries
+    }
+
+    // Nearest expiry on or after `date`. Falls back to the monthly expiry for
+    // underlyings without weekly contracts.
+    pub fn current_weekly(&self, underlying: &str, date: NaiveDate) -> NaiveDate {
+        let monthly = self.current_monthly(underlying, date);
+        self.expiries_between(underlying, date, monthly)
+            .first()
+            .copied()
+            .unwrap_or(monthly)
+    }
+
+    // Expiry after the current weekly one.
+    pub fn next_weekly(&self, underlying: &str, date: NaiveDate) -> NaiveDate {
+        let current = self.current_weekly(underlying, date);
+        self.current_weekly(underlying, current + Duration::days(1))
+    }
+
+    pub fn is_monthly_expiry(&self, underlying: &str, date: NaiveDate) -> bool {
+        self.monthly_expiry(underlying, date.year(), date.month()) == date
+    }
+
+    pub fn is_expiry_day(&self, underlying: &str, date: NaiveDate) -> bool {
+        self.expiries_between(underlying, date, date).contains(&date)
+    }
+
+    // Year fraction till the close of the exchange's session on `expiry`,
+    // taking the calendar's special sessions into account.
+    pub fn time_to_expiry(&self, expiry: NaiveDate, now: DateTime<Local>) -> f64 {
+        let session = MarketSession::for_exchange(self.calendar.exchange).with_calendar(self.calendar.clone());
+        pricing::time_to_expiry(expiry, now, &session)
+    }
+}
+
+impl OptionScrip {
+    pub fn has_valid_expiry(&self, calendar: &ExpiryCalendar) -> bool {
+        calendar.is_expiry_day(&self.name, self.expiry)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Exchange;
+    use crate::calendar::TradingCalendar;
+    use crate::options::{OptionScrip, OptionType};
+    use crate::expiry::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn nse() -> ExpiryCalendar {
+        let mut calendar = TradingCalendar::new(Exchange::NSE);
+        // Thursday of the last week of June 2022.
+        calendar.add_holiday(date(2022, 6, 30), "Holiday");
+        ExpiryCalendar::new(calendar)
+            .with_rules("FINNIFTY", ExpiryRules::new(Some(Weekday::Tue), Weekday::Tue))
+    }
+
+    #[test]
+    fn monthly_expiries() {
+        let expiries = nse();
+        assert_eq!(expiries.monthly_expiry("NIFTY", 2022, 6), date(2022, 6, 29));
+        assert_eq!(expiries.current_monthly("NIFTY", date(2022, 6, 30)), date(2022, 7, 28));
+        assert_eq!(expiries.next_monthly("NIFTY", date(2022, 6, 1)), date(2022, 7, 28));
+        assert_eq!(expiries.monthly_expiry("FINNIFTY", 2022, 6), date(2022, 6, 28));
+    }
+
+    #[test]
+    fn weekly_expiries() {
+        let expiries = nse();
+        assert_eq!(expiries.current_weekly("NIFTY", date(2022, 6, 10)), date(2022, 6, 16));
+        assert_eq!(expiries.next_weekly("NIFTY", date(2022, 6, 24)), date(2022, 7, 7));
+        assert_eq!(
+            expiries.expiries_between("NIFTY", date(2022, 6, 20), date(2022, 7, 1)),
+            vec![date(2022, 6, 23), date(2022, 6, 29)]
+        );
+        assert!(expiries.is_expiry_day("FINNIFTY", date(2022, 6, 14)));
+        assert!(!expiries.is_expiry_day("NIFTY", date(2022, 6, 30)));
+
+        let option = OptionScrip::new("NIFTY", "NSE", "O", date(2022, 6, 29), 16000, OptionType::CE, None);
+        assert!(option.has_valid_expiry(&expiries));
+    }
+
+    #[test]
+    fn time_to_close() {
+        let expiries = nse();
+        let now = FixedOffset::east_opt(19800).unwrap()
+            .with_ymd_and_hms(2022, 6, 29, 9, 30, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        let six_hours = 6.0 / (365.0 * 24.0);
+        assert!((expiries.time_to_expiry(date(2022, 6, 29), now) - six_hours).abs() < 1e-9);
+    }
+}
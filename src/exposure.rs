@@ -0,0 +1,190 @@
+use crate::scrip::Scrip;
+use crate::position::Position;
+use crate::redis_utils::fetch_tickers;
+use crate::tickers::Ticker;
+use crate::info::IndexMetaData;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+// =============================================================================
+//                           Greeks and Exposure
+// =============================================================================
+
+// Sensitivities of a group of holdings, scaled by the quantity held. Delta is
+// in units of the underlying and `delta_notional` is its value at spot.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Exposure {
+    pub delta: f64,
+    pub gamma: f64,
+    pub vega: f64,
+    pub theta: f64,
+    pub delta_notional: f64,
+}
+
+impl AddAssign for Exposure {
+    fn add_assign(&mut self, other: Self) {
+        self.delta += other.delta;
+        self.gamma += other.gamma;
+        self.vega += other.vega;
+        self.theta += other.theta;
+        self.delta_notional += other.delta_notional;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PortfolioExposure {
+    // Keyed by the name of the underlying.
+    pub per_underlying: HashMap<String, Exposure>,
+    pub total: Exposure,
+    // Holdings that could not be priced (options without an underlying or a
+    // valid implied volatility).
+    pub unpriced: Vec<Scrip>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BetaWeightedExposure {
+    // Exposure expressed in units of the index.
+    pub index_units: f64,
+    pub notional: f64,
+    // Underlyings that are neither the index, its constituents nor have a beta.
+    pub excluded: Vec<String>,
+}
+
+fn underlying_name(scrip: &Scrip) -> String {
+    match scrip {
+        Scrip::Stock(s) | Scrip::Index(s) => s.name.clone(),
+        Scrip::Option(o) => match &o.underlying {
+            Some(u) => underlying_name(u),
+            None => o.name.clone(),
+        },
+    }
+}
+
+impl Position {
+    // The holdings and the underlyings of the options held, which are needed
+    // to price them.
+    fn exposure_scrips(&self) -> Vec<Scrip> {
+        let mut scrips: Vec<Scrip> = self.holding.keys().cloned().collect();
+        self.holding.keys().for_each(|x| {
+            if let Scrip::Option(o) = x {
+                if let Some(u) = &o.underlying {
+                    scrips.push(*u.clone());
+                }
+            }
+        });
+        scrips
+    }
+
+    // Greeks of the holdings grouped by their underlying. Stocks and indices
+    // contribute a delta of one per unit.
+    pub fn exposure(&self, rate: f64, now: DateTime<Local>) -> redis::RedisResult<PortfolioExposure> {
+        let tickers = fetch_tickers(&self.exposure_scrips())?;
+        Ok(self.exposure_at(&tickers, rate, now))
+    }
+
+    fn exposure_at(&self, tickers: &HashMap<Scrip, Ticker>, rate: f64, now: DateTime<Local>) -> PortfolioExposure {
+        let mut exposure: PortfolioExposure = Default::default();
+        let ltp = |scrip: &Scrip| tickers.get(scrip).map(|x| x.ltp);
+
+        self.holding.iter().for_each(|(scrip, (quantity, _))| {
+            let quantity = *quantity as f64;
+            let holding_exposure = match scrip {
+                Scrip::Stock(_) | Scrip::Index(_) => ltp(scrip).map(|spot| {
+                    Exposure { delta: quantity, delta_notional: quantity * spot, ..Default::default() }
+                }),
+                Scrip::Option(o) => o.underlying
+                    .as_deref()
+                    .and_then(ltp)
+                    .zip(ltp(scrip))
+                    .and_then(|(spot, price)| o.pricer_at(spot, price, rate, now))
+                    .map(|model| {
+                        let greeks = model.greeks();
+                        Exposure {
+                            delta: quantity * greeks.delta,
+                            gamma: quantity * greeks.gamma,
+                            vega: quantity * greeks.vega,
+                            theta: quantity * greeks.theta,
+                            delta_notional: quantity * greeks.delta * model.spot,
+                        }
+                    }),
+            };
+            let holding_exposure = match holding_exposure {
+                Some(x) => x,
+                None => {
+                    exposure.unpriced.push(scrip.clone());
+                    return;
+                }
+            };
+
+            *exposure.per_underlying.entry(underlying_name(scrip)).or_default() += holding_exposure;
+            exposure.total += holding_exposure;
+        });
+
+        exposure
+    }
+
+    // Delta notional of each underlying scaled by its beta to the index. The
+    // index itself has a beta of one and constituents default to one unless
+    // `betas` says otherwise.
+    pub fn beta_weighted_exposure(
+        &self,
+        index: &Scrip,
+        index_metadata: &IndexMetaData,
+        betas: &HashMap<String, f64>,
+        rate: f64,
+        now: DateTime<Local>,
+    ) -> redis::RedisResult<BetaWeightedExposure> {
+        let index_name = underlying_name(index);
+        let mut weighted: BetaWeightedExposure = Default::default();
+
+        let mut scrips = self.exposure_scrips();
+        scrips.push(index.clone());
+        let tickers = fetch_tickers(&scrips)?;
+
+        self.exposure_at(&tickers, rate, now).per_underlying.into_iter().for_each(|(name, x)| {
+            let beta = match betas.get(&name) {
+                Some(b) => *b,
+                None if name == index_name || index_metadata.constituents.contains_key(&name) => 1.0,
+                None => {
+                    weighted.excluded.push(name);
+                    return;
+                }
+            };
+            weighted.notional += x.delta_notional * beta;
+        });
+
+        let index_ltp = tickers.get(index).map_or(0.0, |x| x.ltp);
+        if index_ltp != 0.0 {
+            weighted.index_units = weighted.notional / index_ltp;
+        }
+        Ok(weighted)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use chrono::{Local, NaiveDate};
+
+    #[test]
+    fn grouped_by_underlying() {
+        let underlying = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let expired = NaiveDate::from_ymd_opt(2020, 1, 30).unwrap();
+        let call = OptionScrip::new("TEST", "NSE", "O", expired, 390, OptionType::CE, Some(underlying.clone()));
+
+        let mut position: Position = Default::default();
+        position.update_holding(underlying, 10, 400.0);
+        position.update_holding(Scrip::Option(call), -5, 10.0);
+
+        // The expired ITM call has a delta of one
+        let exposure = position.exposure(0.05, Local::now()).unwrap();
+        assert_eq!(exposure.per_underlying.len(), 1);
+        assert_eq!(exposure.total.delta, 5.0);
+        assert!((exposure.total.delta_notional - 5.0 * 400.23).abs() < 1e-9);
+    }
+}
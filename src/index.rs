@@ -0,0 +1,241 @@
+use crate::scrip::{Scrip, ExchangeType};
+use crate::stock::StockScrip;
+use crate::tickers::Ticker;
+use crate::info::{IndexMetaData, parse_exchange};
+use crate::redis_utils::fetch_tickers;
+use crate::error::MetadataError;
+use std::collections::HashMap;
+
+// =============================================================================
+//                            Index Reconstruction
+// =============================================================================
+
+// `ohlc.close` is taken as the previous close, and the weights as of that
+// close.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstituentMove {
+    pub name: String,
+    pub weight: f64,
+    pub ltp: f64,
+    pub previous_close: f64,
+    // Fractional change since the previous close.
+    pub change: f64,
+    // Index points added by this constituent.
+    pub contribution: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Breadth {
+    pub advances: usize,
+    pub declines: usize,
+    pub unchanged: usize,
+}
+
+impl Breadth {
+    // `None` when nothing declined.
+    pub fn advance_decline_ratio(&self) -> Option<f64> {
+        match self.declines {
+            0 => None,
+            declines => Some(self.advances as f64 / declines as f64),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexReconstruction {
+    pub index: String,
+    // Previous close of the index, which the synthetic value moves from.
+    pub base: f64,
+    pub value: f64,
+    pub constituents: Vec<ConstituentMove>,
+    // Constituents without a usable ticker, left out of the value.
+    pub missing: Vec<String>,
+}
+
+impl IndexReconstruction {
+    // Weights are normalised over the constituents that have tickers.
+    pub fn from_tickers(metadata: &IndexMetaData, base: f64, tickers: &HashMap<String, Ticker>) -> Self {
+        let mut missing: Vec<String> = Vec::new();
+        let available: Vec<(&String, f64, &Ticker)> = metadata.constituents
+            .iter()
+            .filter_map(|(name, weight)| match tickers.get(name) {
+                Some(ticker) if ticker.ohlc.close > 0.0 => Some((name, *weight, ticker)),
+                _ => { missing.push(name.to_string()); None },
+            })
+            .collect();
+        missing.sort();
+
+        let total_weight: f64 = available.iter().map(|x| x.1).sum();
+        let mut constituents: Vec<ConstituentMove> = available
+            .iter()
+            .map(|(name, weight, ticker)| {
+                let change = ticker.ltp / ticker.ohlc.close - 1.0;
+                ConstituentMove {
+                    name: name.to_string(),
+                    weight: *weight,
+                    ltp: ticker.ltp,
+                    previous_close: ticker.ohlc.close,
+                    change,
+                    contribution: match total_weight > 0.0 {
+                        true => base * weight / total_weight * change,
+                        false => 0.0,
+                    },
+                }
+            })
+            .collect();
+        constituents.sort_by(|a, b| b.contribution.abs().total_cmp(&a.contribution.abs()));
+
+        let value = base + constituents.iter().map(|x| x.contribution).sum::<f64>();
+        Self {
+            index: metadata.scrip.clone(),
+            base,
+            value,
+            constituents,
+            missing,
+        }
+    }
+
+    // Fetches the index and its constituents from redis. Constituents trade as
+    // stocks on the index's exchange.
+    pub fn reconstruct(index: &Scrip, metadata: &IndexMetaData) -> Result<Self, MetadataError> {
+        let exchange = parse_exchange(&metadata.exchange)?;
+        let constituents: Vec<(String, Scrip)> = metadata.constituents
+            .keys()
+            .map(|name| {
+                let scrip = StockScrip { name: name.to_string(), exchange, exchange_type: ExchangeType::Cash };
+                (name.to_string(), Scrip::Stock(scrip))
+            })
+            .collect();
+
+        let mut scrips: Vec<Scrip> = constituents.iter().map(|(_, x)| x.clone()).collect();
+        scrips.push(index.clone());
+        let mut fetched = fetch_tickers(&scrips).map_err(|e| MetadataError::Transport(e.to_string()))?;
+
+        let tickers: HashMap<String, Ticker> = constituents
+            .into_iter()
+            .filter_map(|(name, x)| Some((name, fetched.remove(&x)?)))
+            .collect();
+        let index_close = fetched.get(index).map_or(0.0, |x| x.ohlc.close);
+        Ok(Self::from_tickers(metadata, index_close, &tickers))
+    }
+
+    pub fn points_change(&self) -> f64 {
+        self.value - self.base
+    }
+
+    // Synthetic value less the actual index value, in points.
+    pub fn tracking_difference(&self, actual: f64) -> f64 {
+        self.value - actual
+    }
+
+    // Constituents with the largest absolute contribution first.
+    pub fn top_contributors(&self, n: usize) -> &[ConstituentMove] {
+        &self.constituents[..n.min(self.constituents.len())]
+    }
+
+    pub fn breadth(&self) -> Breadth {
+        self.constituents.iter().fold(Breadth::default(), |mut breadth, x| {
+            match x.ltp.partial_cmp(&x.previous_close) {
+                Some(std::cmp::Ordering::Greater) => breadth.advances += 1,
+                Some(std::cmp::Ordering::Less) => breadth.declines += 1,
+                _ => breadth.unchanged += 1,
+            }
+            breadth
+        })
+    }
+}
+
+// Standard deviation of the difference in returns between the synthetic and
+// the actual index, over consecutive observations of both. `None` when an
+// observation is zero, which has no return.
+pub fn tracking_error(synthetic: &[f64], actual: &[f64]) -> Option<f64> {
+    let n = synthetic.len().min(actual.len());
+    if synthetic[..n].iter().chain(&actual[..n]).any(|x| *x == 0.0) {
+        return None;
+    }
+    let differences: Vec<f64> = synthetic
+        .windows(2)
+        .zip(actual.windows(2))
+        .map(|(s, a)| (s[1] / s[0] - 1.0) - (a[1] / a[0] - 1.0))
+        .collect();
+    if differences.len() < 2 {
+        return None;
+    }
+
+    let n = differences.len() as f64;
+    let mean = differences.iter().sum::<f64>() / n;
+    Some((differences.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt())
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::tickers::{Ticker, OHLC};
+    use crate::info::IndexMetaData;
+    use crate::index::*;
+    use chrono::FixedOffset;
+
+    fn ticker(ltp: f64, close: f64) -> Ticker {
+        Ticker { ltp, ohlc: OHLC { close, ..Default::default() }, ..Default::default() }
+    }
+
+    fn metadata() -> IndexMetaData {
+        IndexMetaData {
+            scrip: String::from("TEST50"),
+            open_time: None,
+            close_time: None,
+            currency: String::from("INR"),
+            exchange: String::from("NSE"),
+            timezone: FixedOffset::east_opt(19800).unwrap(),
+            constituents: HashMap::from([
+                (String::from("A"), 60.0),
+                (String::from("B"), 30.0),
+                (String::from("C"), 10.0),
+                (String::from("D"), 5.0),
+            ]),
+        }
+    }
+
+    #[test]
+    fn synthetic_value() {
+        let tickers = HashMap::from([
+            (String::from("A"), ticker(110.0, 100.0)),
+            (String::from("B"), ticker(190.0, 200.0)),
+            (String::from("C"), ticker(50.0, 50.0)),
+        ]);
+        let index = IndexReconstruction::from_tickers(&metadata(), 1000.0, &tickers);
+
+        // 0.6 * 10% - 0.3 * 5% on a base of 1000.
+        assert!((index.points_change() - 45.0).abs() < 1e-9);
+        assert_eq!(index.missing, vec![String::from("D")]);
+        assert_eq!(index.top_contributors(1)[0].name, "A");
+        assert_eq!(index.breadth(), Breadth { advances: 1, declines: 1, unchanged: 1 });
+        assert!((index.tracking_difference(1040.0) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tracking_error_of_identical_series() {
+        let series = [100.0, 101.0, 99.5, 102.0];
+        assert_eq!(tracking_error(&series, &series), Some(0.0));
+        assert_eq!(tracking_error(&series[..2], &series[..2]), None);
+        assert_eq!(tracking_error(&[100.0, 0.0, 99.5], &series), None);
+    }
+
+    #[test]
+    fn reconstruct_from_redis() {
+        use crate::scrip::Scrip;
+        use crate::stock::IndexScrip;
+        use crate::test_util::TEST_TICKER_1;
+
+        // Every ticker is TEST_TICKER_1 in tests, so the index moves with it.
+        let index = Scrip::Index(IndexScrip::new("TEST50", "NSE", "I"));
+        let reconstruction = IndexReconstruction::reconstruct(&index, &metadata()).unwrap();
+        assert!(reconstruction.missing.is_empty());
+        assert_eq!(reconstruction.constituents.len(), 4);
+        assert_eq!(reconstruction.base, TEST_TICKER_1.ohlc.close);
+        assert!((reconstruction.value - TEST_TICKER_1.ltp).abs() < 1e-9);
+    }
+}
@@ -1,31 +1,86 @@
 use aws_sdk_dynamodb::model::AttributeValue;
 use std::collections::HashMap;
 use chrono::prelude::*;
+use crate::error::MetadataError;
+use crate::scrip::Exchange;
 
 pub static TABLE_NAME: &str = "scrip_info";
+pub static DEFAULT_TICK_SIZE: f64 = 0.05;
 
-pub fn format_timezone(timezone: &str) -> FixedOffset {
-    let direction = timezone.chars().next().unwrap();
-    let hours = timezone[1..3].parse::<i32>().unwrap();
-    let minutes = timezone[4..6].parse::<i32>().unwrap();
+fn malformed(key: &str, reason: &str) -> MetadataError {
+    MetadataError::Malformed(key.to_string(), reason.to_string())
+}
+
+// Unlike `Exchange::from`, fails instead of panicking on unknown exchanges.
+// Case insensitive.
+pub fn parse_exchange(exchange: &str) -> Result<Exchange, MetadataError> {
+    match exchange.to_uppercase().as_str() {
+        "NSE" => Ok(Exchange::NSE),
+        "BSE" => Ok(Exchange::BSE),
+        "MCX" => Ok(Exchange::MCX),
+        exch => Err(malformed("exchange", &format!("unknown exchange {}", exch))),
+    }
+}
+
+// Expects "+HH:MM" or "-HH:MM".
+pub fn format_timezone(timezone: &str) -> Result<FixedOffset, MetadataError> {
+    let invalid = || malformed("timezone", &format!("expected +HH:MM, found {}", timezone));
+    let hours = timezone.get(1..3).and_then(|x| x.parse::<i32>().ok()).ok_or_else(invalid)?;
+    let minutes = timezone.get(4..6).and_then(|x| x.parse::<i32>().ok()).ok_or_else(invalid)?;
 
     let seconds = (hours*60 + minutes) * 60;
-    match direction {
-        '-' => FixedOffset::west(seconds),
-        '+' => FixedOffset::east(seconds),
-        _ => panic!("Invalid timezone format => {}", timezone),
+    let offset = match timezone.chars().next() {
+        Some('-') => FixedOffset::west_opt(seconds),
+        Some('+') => FixedOffset::east_opt(seconds),
+        _ => None,
+    };
+    offset.ok_or_else(invalid)
+}
+
+// Expects "HHMM".
+pub fn format_time(time: &str) -> Result<NaiveTime, MetadataError> {
+    match time.len() == 4 && time.chars().all(|c| c.is_ascii_digit()) {
+        true => NaiveTime::parse_from_str(time, "%H%M").ok(),
+        false => None,
+    }.ok_or_else(|| malformed("time", &format!("expected HHMM, found {}", time)))
+}
+
+fn as_string<'a>(key: &str, attribute: &'a AttributeValue) -> Result<&'a String, MetadataError> {
+    match attribute {
+        AttributeValue::S(value) => Ok(value),
+        _ => Err(malformed(key, "expected a string")),
+    }
+}
+
+fn as_date(key: &str, attribute: &AttributeValue) -> Result<NaiveDate, MetadataError> {
+    let date = as_string(key, attribute)?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| malformed(key, &format!("expected YYYY-MM-DD, found {}", date)))
+}
+
+fn as_number(key: &str, attribute: &AttributeValue) -> Result<f64, MetadataError> {
+    match attribute {
+        AttributeValue::N(value) => value
+            .parse::<f64>()
+            .map_err(|_| malformed(key, &format!("invalid number {}", value))),
+        _ => Err(malformed(key, "expected a number")),
     }
 }
 
-pub fn format_time(time: &str) -> String {
-    format!("{}.{}", &time[0..2], &time[2..4])
+// A whole, non-negative number such as a quantity.
+fn as_count(key: &str, attribute: &AttributeValue) -> Result<u32, MetadataError> {
+    let value = as_number(key, attribute)?;
+    match value.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&value) {
+        true => Ok(value as u32),
+        false => Err(malformed(key, &format!("expected a whole quantity, found {}", value))),
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct IndexMetaData {
     pub scrip: String,
-    pub open_time: String,
-    pub close_time: String,
+    pub open_time: Option<NaiveTime>,
+    pub close_time: Option<NaiveTime>,
     pub currency: String,
     pub exchange: String,
     pub timezone: FixedOffset,
@@ -33,53 +88,80 @@ pub struct IndexMetaData {
 }
 
 impl IndexMetaData {
-    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Self {
+    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Result<Self, MetadataError> {
         let mut index_meta_data = IndexMetaData {
             scrip: String::new(),
-            open_time: String::new(),
-            close_time: String::new(),
+            open_time: None,
+            close_time: None,
             currency: String::new(),
             exchange: String::new(),
-            timezone: FixedOffset::east(0),
+            timezone: FixedOffset::east_opt(0).unwrap(),
             constituents: HashMap::new(),
         };
-        items.iter().for_each(|(k, v)| index_meta_data.update(k, v));
-        index_meta_data
+        items.iter().try_for_each(|(k, v)| index_meta_data.update(k, v))?;
+        Ok(index_meta_data)
     }
-    pub fn update(&mut self, key: &str, attribute: &AttributeValue) {
+    pub fn update(&mut self, key: &str, attribute: &AttributeValue) -> Result<(), MetadataError> {
         match key {
-            "scrip" => if let AttributeValue::S(scrip) = attribute {
-                self.scrip = scrip.to_string();
-            }
-            "openTime" => if let AttributeValue::S(open_time) = attribute {
-                self.open_time = format_time(open_time);
-            }
-            "closeTime" => if let AttributeValue::S(close_time) = attribute {
-                self.close_time = format_time(close_time);
-            }
-            "currency" => if let AttributeValue::S(currency) = attribute {
-                self.currency = currency.to_uppercase();
-            }
-            "exchange" => if let AttributeValue::S(exchange) = attribute {
-                self.exchange = exchange.to_uppercase();
-            }
-            "timezone" => if let AttributeValue::S(timezone) = attribute {
-                self.timezone = format_timezone(timezone);
-            }
-            "constituents" => if let AttributeValue::M(constituents) = attribute {
-                self.constituents = HashMap::new();
-                constituents
-                    .iter()
-                    .for_each(|(scrip, information)| {
-                        if let AttributeValue::N(weight) = information {
-                            let weight: f64 = weight.parse::<f64>().unwrap();
-                            self.constituents.insert(scrip.to_string(), weight);
-                        }
-                    });
+            "scrip" => self.scrip = as_string(key, attribute)?.to_string(),
+            "openTime" => self.open_time = Some(format_time(as_string(key, attribute)?)?),
+            "closeTime" => self.close_time = Some(format_time(as_string(key, attribute)?)?),
+            "currency" => self.currency = as_string(key, attribute)?.to_uppercase(),
+            "exchange" => self.exchange = as_string(key, attribute)?.to_uppercase(),
+            "timezone" => self.timezone = format_timezone(as_string(key, attribute)?)?,
+            "constituents" => match attribute {
+                AttributeValue::M(constituents) => {
+                    self.constituents = constituents
+                        .iter()
+                        .map(|(scrip, weight)| Ok((scrip.to_string(), as_number(key, weight)?)))
+                        .collect::<Result<HashMap<String, f64>, MetadataError>>()?;
+                }
+                _ => return Err(malformed(key, "expected a map")),
             }
             _ => (),
         }
-        
+        Ok(())
+    }
+}
+
+// =============================================================================
+//                               CONTRACT SPECIFICATION
+// =============================================================================
+
+// Trading attributes shared by stocks, options and futures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContractSpec {
+    pub lot_size: u32,
+    pub tick_size: f64,
+    pub lower_circuit: Option<f64>,
+    pub upper_circuit: Option<f64>,
+    pub freeze_quantity: Option<u32>,
+}
+
+impl Default for ContractSpec {
+    fn default() -> Self {
+        Self {
+            lot_size: 1,
+            tick_size: DEFAULT_TICK_SIZE,
+            lower_circuit: None,
+            upper_circuit: None,
+            freeze_quantity: None,
+        }
+    }
+}
+
+impl ContractSpec {
+    // Returns whether `key` is a contract attribute.
+    pub fn update(&mut self, key: &str, attribute: &AttributeValue) -> Result<bool, MetadataError> {
+        match key {
+            "lotSize" => self.lot_size = as_count(key, attribute)?,
+            "tickSize" => self.tick_size = as_number(key, attribute)?,
+            "lowerCircuit" => self.lower_circuit = Some(as_number(key, attribute)?),
+            "upperCircuit" => self.upper_circuit = Some(as_number(key, attribute)?),
+            "freezeQuantity" => self.freeze_quantity = Some(as_count(key, attribute)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
     }
 }
 
@@ -90,53 +172,146 @@ impl IndexMetaData {
 #[derive(Clone, Debug)]
 pub struct StockMetaData {
     pub scrip: String,
-    pub open_time: String,
-    pub close_time: String,
+    pub open_time: Option<NaiveTime>,
+    pub close_time: Option<NaiveTime>,
     pub currency: String,
     pub exchange: String,
     pub timezone: FixedOffset,
     pub free_float_market_cap: f64,
+    pub contract: ContractSpec,
 }
 
 impl StockMetaData {
-    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Self {
+    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Result<Self, MetadataError> {
         let mut stock_meta_data = StockMetaData {
             scrip: String::new(),
-            open_time: String::new(),
-            close_time: String::new(),
+            open_time: None,
+            close_time: None,
             currency: String::new(),
             exchange: String::new(),
-            timezone: FixedOffset::east(0),
+            timezone: FixedOffset::east_opt(0).unwrap(),
             free_float_market_cap: 0.0,
+            contract: Default::default(),
         };
-        items.iter().for_each(|(k, v)| stock_meta_data.update(k, v));
-        stock_meta_data
+        items.iter().try_for_each(|(k, v)| stock_meta_data.update(k, v))?;
+        Ok(stock_meta_data)
     }
-    pub fn update(&mut self, key: &str, attribute: &AttributeValue) {
+    pub fn update(&mut self, key: &str, attribute: &AttributeValue) -> Result<(), MetadataError> {
         match key {
-            "scrip" => if let AttributeValue::S(scrip) = attribute {
-                self.scrip = scrip.to_string();
-            }
-            "openTime" => if let AttributeValue::S(open_time) = attribute {
-                self.open_time = format_time(open_time);
-            }
-            "closeTime" => if let AttributeValue::S(close_time) = attribute {
-                self.close_time = format_time(close_time);
-            }
-            "currency" => if let AttributeValue::S(currency) = attribute {
-                self.currency = currency.to_uppercase();
-            }
-            "exchange" => if let AttributeValue::S(exchange) = attribute {
-                self.exchange = exchange.to_uppercase();
-            }
-            "freeFloatMarketCap" => if let AttributeValue::N(ff_mcap) = attribute {
-                self.free_float_market_cap = ff_mcap.parse::<f64>().unwrap();
-            }
-            "timezone" => if let AttributeValue::S(timezone) = attribute {
-                self.timezone = format_timezone(timezone);
-            }
-            _ => (),
+            "scrip" => self.scrip = as_string(key, attribute)?.to_string(),
+            "openTime" => self.open_time = Some(format_time(as_string(key, attribute)?)?),
+            "closeTime" => self.close_time = Some(format_time(as_string(key, attribute)?)?),
+            "currency" => self.currency = as_string(key, attribute)?.to_uppercase(),
+            "exchange" => self.exchange = as_string(key, attribute)?.to_uppercase(),
+            "freeFloatMarketCap" => self.free_float_market_cap = as_number(key, attribute)?,
+            "timezone" => self.timezone = format_timezone(as_string(key, attribute)?)?,
+            _ => { self.contract.update(key, attribute)?; },
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+//                               OPTION METADATA
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct OptionMetaData {
+    pub scrip: String,
+    pub underlying: String,
+    pub open_time: Option<NaiveTime>,
+    pub close_time: Option<NaiveTime>,
+    pub currency: String,
+    pub exchange: String,
+    pub timezone: FixedOffset,
+    pub expiry: Option<NaiveDate>,
+    pub strike: f64,
+    pub option_type: String,
+    pub contract: ContractSpec,
+}
+
+impl OptionMetaData {
+    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Result<Self, MetadataError> {
+        let mut option_meta_data = OptionMetaData {
+            scrip: String::new(),
+            underlying: String::new(),
+            open_time: None,
+            close_time: None,
+            currency: String::new(),
+            exchange: String::new(),
+            timezone: FixedOffset::east_opt(0).unwrap(),
+            expiry: None,
+            strike: 0.0,
+            option_type: String::new(),
+            contract: Default::default(),
+        };
+        items.iter().try_for_each(|(k, v)| option_meta_data.update(k, v))?;
+        Ok(option_meta_data)
+    }
+    pub fn update(&mut self, key: &str, attribute: &AttributeValue) -> Result<(), MetadataError> {
+        match key {
+            "scrip" => self.scrip = as_string(key, attribute)?.to_string(),
+            "underlying" => self.underlying = as_string(key, attribute)?.to_string(),
+            "openTime" => self.open_time = Some(format_time(as_string(key, attribute)?)?),
+            "closeTime" => self.close_time = Some(format_time(as_string(key, attribute)?)?),
+            "currency" => self.currency = as_string(key, attribute)?.to_uppercase(),
+            "exchange" => self.exchange = as_string(key, attribute)?.to_uppercase(),
+            "timezone" => self.timezone = format_timezone(as_string(key, attribute)?)?,
+            "expiry" => self.expiry = Some(as_date(key, attribute)?),
+            "strike" => self.strike = as_number(key, attribute)?,
+            "optionType" => self.option_type = as_string(key, attribute)?.to_uppercase(),
+            _ => { self.contract.update(key, attribute)?; },
         }
+        Ok(())
+    }
+}
+
+// =============================================================================
+//                               FUTURE METADATA
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct FutureMetaData {
+    pub scrip: String,
+    pub underlying: String,
+    pub open_time: Option<NaiveTime>,
+    pub close_time: Option<NaiveTime>,
+    pub currency: String,
+    pub exchange: String,
+    pub timezone: FixedOffset,
+    pub expiry: Option<NaiveDate>,
+    pub contract: ContractSpec,
+}
+
+impl FutureMetaData {
+    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Result<Self, MetadataError> {
+        let mut future_meta_data = FutureMetaData {
+            scrip: String::new(),
+            underlying: String::new(),
+            open_time: None,
+            close_time: None,
+            currency: String::new(),
+            exchange: String::new(),
+            timezone: FixedOffset::east_opt(0).unwrap(),
+            expiry: None,
+            contract: Default::default(),
+        };
+        items.iter().try_for_each(|(k, v)| future_meta_data.update(k, v))?;
+        Ok(future_meta_data)
+    }
+    pub fn update(&mut self, key: &str, attribute: &AttributeValue) -> Result<(), MetadataError> {
+        match key {
+            "scrip" => self.scrip = as_string(key, attribute)?.to_string(),
+            "underlying" => self.underlying = as_string(key, attribute)?.to_string(),
+            "openTime" => self.open_time = Some(format_time(as_string(key, attribute)?)?),
+            "closeTime" => self.close_time = Some(format_time(as_string(key, attribute)?)?),
+            "currency" => self.currency = as_string(key, attribute)?.to_uppercase(),
+            "exchange" => self.exchange = as_string(key, attribute)?.to_uppercase(),
+            "timezone" => self.timezone = format_timezone(as_string(key, attribute)?)?,
+            "expiry" => self.expiry = Some(as_date(key, attribute)?),
+            _ => { self.contract.update(key, attribute)?; },
+        }
+        Ok(())
     }
 }
 
@@ -148,24 +323,160 @@ impl StockMetaData {
 pub enum MetaData {
     Index(IndexMetaData),
     Stock(StockMetaData),
+    Option(OptionMetaData),
+    Future(FutureMetaData),
 }
 
 impl MetaData {
-    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Self {
-        match items.get("type") {
-            Some(t) => {
-                if let AttributeValue::S(ty) = t {
-                    return match ty.as_str() {
-                        "cash" => MetaData::Stock(StockMetaData::from_response(items)),
-                        "index" => MetaData::Index(IndexMetaData::from_response(items)),
-                        attr => panic!("Invalid `type` key -> {}", attr),
-                    }
+    pub fn from_response(items: &HashMap<String, AttributeValue>) -> Result<Self, MetadataError> {
+        let ty = items.get("type").ok_or_else(|| malformed("type", "missing"))?;
+        match as_string("type", ty)?.as_str() {
+            "cash" => Ok(MetaData::Stock(StockMetaData::from_response(items)?)),
+            "index" => Ok(MetaData::Index(IndexMetaData::from_response(items)?)),
+            "option" => Ok(MetaData::Option(OptionMetaData::from_response(items)?)),
+            "future" => Ok(MetaData::Future(FutureMetaData::from_response(items)?)),
+            attr => Err(malformed("type", &format!("unknown type {}", attr))),
+        }
+    }
+
+    // Item in the layout of the DynamoDB table, as read by `from_response`.
+    pub fn to_item(&self) -> HashMap<String, AttributeValue> {
+        let string = |x: &str| AttributeValue::S(x.to_string());
+        let number = |x: f64| AttributeValue::N(x.to_string());
+        let mut item: HashMap<String, AttributeValue> = HashMap::new();
+
+        let (ty, scrip, currency) = match self {
+            MetaData::Index(i) => ("index", &i.scrip, &i.currency),
+            MetaData::Stock(s) => ("cash", &s.scrip, &s.currency),
+            MetaData::Option(o) => ("option", &o.scrip, &o.currency),
+            MetaData::Future(f) => ("future", &f.scrip, &f.currency),
+        };
+        item.insert(String::from("type"), string(ty));
+        item.insert(String::from("scrip"), string(scrip));
+        item.insert(String::from("currency"), string(currency));
+        item.insert(String::from("exchange"), string(self.exchange()));
+        item.insert(String::from("timezone"), string(&self.timezone().to_string()));
+        if let Some(time) = self.open_time() {
+            item.insert(String::from("openTime"), string(&time.format("%H%M").to_string()));
+        }
+        if let Some(time) = self.close_time() {
+            item.insert(String::from("closeTime"), string(&time.format("%H%M").to_string()));
+        }
+
+        let contract = match self {
+            MetaData::Index(i) => {
+                let constituents = i.constituents.iter().map(|(k, v)| (k.to_string(), number(*v))).collect();
+                item.insert(String::from("constituents"), AttributeValue::M(constituents));
+                return item;
+            }
+            MetaData::Stock(s) => {
+                item.insert(String::from("freeFloatMarketCap"), number(s.free_float_market_cap));
+                &s.contract
+            }
+            MetaData::Option(o) => {
+                item.insert(String::from("underlying"), string(&o.underlying));
+                item.insert(String::from("strike"), number(o.strike));
+                item.insert(String::from("optionType"), string(&o.option_type));
+                if let Some(expiry) = o.expiry {
+                    item.insert(String::from("expiry"), string(&expiry.format("%Y-%m-%d").to_string()));
+                }
+                &o.contract
+            }
+            MetaData::Future(f) => {
+                item.insert(String::from("underlying"), string(&f.underlying));
+                if let Some(expiry) = f.expiry {
+                    item.insert(String::from("expiry"), string(&expiry.format("%Y-%m-%d").to_string()));
                 }
-                panic!("`type` key not in String format.")
+                &f.contract
             }
-            None => panic!("Key `type` missing. Unable to identify scrip type!"),
+        };
+        item.insert(String::from("lotSize"), number(contract.lot_size as f64));
+        item.insert(String::from("tickSize"), number(contract.tick_size));
+        if let Some(lower) = contract.lower_circuit {
+            item.insert(String::from("lowerCircuit"), number(lower));
+        }
+        if let Some(upper) = contract.upper_circuit {
+            item.insert(String::from("upperCircuit"), number(upper));
+        }
+        if let Some(freeze) = contract.freeze_quantity {
+            item.insert(String::from("freezeQuantity"), number(freeze as f64));
+        }
+        item
+    }
+
+    pub fn exchange(&self) -> &str {
+        match self {
+            MetaData::Index(i) => &i.exchange,
+            MetaData::Stock(s) => &s.exchange,
+            MetaData::Option(o) => &o.exchange,
+            MetaData::Future(f) => &f.exchange,
+        }
+    }
+
+    pub fn open_time(&self) -> Option<NaiveTime> {
+        match self {
+            MetaData::Index(i) => i.open_time,
+            MetaData::Stock(s) => s.open_time,
+            MetaData::Option(o) => o.open_time,
+            MetaData::Future(f) => f.open_time,
+        }
+    }
+
+    pub fn close_time(&self) -> Option<NaiveTime> {
+        match self {
+            MetaData::Index(i) => i.close_time,
+            MetaData::Stock(s) => s.close_time,
+            MetaData::Option(o) => o.close_time,
+            MetaData::Future(f) => f.close_time,
+        }
+    }
+
+    pub fn timezone(&self) -> FixedOffset {
+        match self {
+            MetaData::Index(i) => i.timezone,
+            MetaData::Stock(s) => s.timezone,
+            MetaData::Option(o) => o.timezone,
+            MetaData::Future(f) => f.timezone,
+        }
+    }
+
+    // `None` for indices, which are not traded.
+    pub fn contract(&self) -> Option<&ContractSpec> {
+        match self {
+            MetaData::Index(_) => None,
+            MetaData::Stock(s) => Some(&s.contract),
+            MetaData::Option(o) => Some(&o.contract),
+            MetaData::Future(f) => Some(&f.contract),
+        }
+    }
+
+    pub fn contract_mut(&mut self) -> Option<&mut ContractSpec> {
+        match self {
+            MetaData::Index(_) => None,
+            MetaData::Stock(s) => Some(&mut s.contract),
+            MetaData::Option(o) => Some(&mut o.contract),
+            MetaData::Future(f) => Some(&mut f.contract),
         }
     }
+
+    // Indices report a lot and tick of a single unit.
+    pub fn lot_size(&self) -> u32 {
+        self.contract().map_or(1, |x| x.lot_size)
+    }
+
+    pub fn tick_size(&self) -> f64 {
+        self.contract().map_or(DEFAULT_TICK_SIZE, |x| x.tick_size)
+    }
+
+    // (lower, upper) circuit limits, when both are known.
+    pub fn price_band(&self) -> Option<(f64, f64)> {
+        let contract = self.contract()?;
+        contract.lower_circuit.zip(contract.upper_circuit)
+    }
+
+    pub fn freeze_quantity(&self) -> Option<u32> {
+        self.contract()?.freeze_quantity
+    }
 }
 
 #[cfg(test)]
@@ -173,30 +484,135 @@ mod tests {
     use crate::IndexScrip;
     use crate::scrip::Scrip;
     use crate::stock::StockScrip;
+    use crate::info::*;
+    use crate::provider::{InMemoryProvider, MetadataProvider};
+
+    fn test_provider() -> InMemoryProvider {
+        InMemoryProvider::from_json(r#"[
+            {"scrip": "SBIN", "type": "cash", "exchange": "nse", "currency": "inr",
+             "openTime": "0915", "closeTime": "1530", "timezone": "+05:30",
+             "freeFloatMarketCap": 250000.5},
+            {"scrip": "NIFTY", "type": "index", "exchange": "nse", "currency": "inr",
+             "openTime": "0915", "closeTime": "1530", "timezone": "+05:30",
+             "constituents": {"SBIN": 2.5, "INFY": 6.1}}
+        ]"#).unwrap()
+    }
 
-    // =========================================================================
-    // These tests passes only if the program has access to the dynamodb table.
     #[test]
     pub fn sbin_call() {
         let sbin = Scrip::Stock(StockScrip::new("SBIN", "NSE", "C"));
-        let sbin_info = sbin.get_metadata();
+        let sbin_info = sbin.get_metadata_from(&test_provider());
         match sbin_info {
-            Some(info) => {
-                dbg!(info);
+            Ok(MetaData::Stock(info)) => {
+                assert_eq!(info.close_time, Some(NaiveTime::from_hms_opt(15, 30, 0).unwrap()));
+                assert_eq!(info.timezone, FixedOffset::east_opt(19800).unwrap());
             },
-            None => panic!("Empty SBI"),
+            info => panic!("Unexpected SBI metadata: {:?}", info),
         }
     }
     #[test]
     pub fn nifty50_call() {
         let nifty = Scrip::Index(IndexScrip::new("NIFTY", "NSE", "I"));
-        let nifty_info = nifty.get_metadata();
+        let nifty_info = nifty.get_metadata_from(&test_provider());
         match nifty_info {
-            Some(info) => {
-                dbg!(info);
+            Ok(MetaData::Index(info)) => {
+                assert_eq!(info.constituents.len(), 2);
             },
-            None => panic!("Empty Nifty"),
+            info => panic!("Unexpected Nifty metadata: {:?}", info),
+        }
+    }
+
+    // =========================================================================
+    // These tests passes only if the program has access to the dynamodb table.
+    #[test]
+    #[ignore]
+    pub fn sbin_dynamo_call() {
+        let sbin = Scrip::Stock(StockScrip::new("SBIN", "NSE", "C"));
+        if let Err(e) = sbin.get_metadata() {
+            panic!("Empty SBI: {}", e);
+        }
+    }
+    #[test]
+    #[ignore]
+    pub fn nifty50_dynamo_call() {
+        let nifty = Scrip::Index(IndexScrip::new("NIFTY", "NSE", "I"));
+        if let Err(e) = nifty.get_metadata() {
+            panic!("Empty Nifty: {}", e);
         }
     }
     // =========================================================================
+
+    #[test]
+    pub fn item_roundtrip() {
+        let provider = InMemoryProvider::from_json(r#"[
+            {"scrip": "NIFTY:NSE:O:30/06/2022:16000:CE", "type": "option", "exchange": "nse",
+             "currency": "inr", "closeTime": "1530", "timezone": "+05:30", "expiry": "2022-06-30",
+             "strike": 16000, "optionType": "ce", "lotSize": 50, "upperCircuit": 120.5}
+        ]"#).unwrap();
+        let option = provider.get("NIFTY:NSE:O:30/06/2022:16000:CE").unwrap();
+        match MetaData::from_response(&option.to_item()) {
+            Ok(MetaData::Option(o)) => {
+                assert_eq!(o.close_time, Some(NaiveTime::from_hms_opt(15, 30, 0).unwrap()));
+                assert_eq!(o.timezone, FixedOffset::east_opt(19800).unwrap());
+                assert_eq!((o.strike, o.contract.lot_size, o.contract.upper_circuit), (16000.0, 50, Some(120.5)));
+                assert_eq!(o.expiry, NaiveDate::from_ymd_opt(2022, 6, 30));
+            }
+            m => panic!("Unexpected option metadata: {:?}", m),
+        }
+    }
+
+    #[test]
+    pub fn timezone_and_time() {
+        assert_eq!(format_timezone("+05:30"), Ok(FixedOffset::east_opt(19800).unwrap()));
+        assert!(format_timezone("IST").is_err());
+        assert_eq!(format_time("0915"), Ok(NaiveTime::from_hms_opt(9, 15, 0).unwrap()));
+        assert!(format_time("9:15").is_err());
+        assert!(format_time("2575").is_err());
+    }
+
+    #[test]
+    pub fn malformed_response() {
+        let mut items = HashMap::from([
+            (String::from("type"), AttributeValue::S(String::from("index"))),
+            (String::from("constituents"), AttributeValue::M(HashMap::from([
+                (String::from("SBIN"), AttributeValue::N(String::from("2.5"))),
+                (String::from("INFY"), AttributeValue::S(String::from("heavy"))),
+            ]))),
+        ]);
+        assert_eq!(
+            MetaData::from_response(&items).err(),
+            Some(MetadataError::Malformed(String::from("constituents"), String::from("expected a number")))
+        );
+
+        items.remove("type");
+        assert!(matches!(MetaData::from_response(&items), Err(MetadataError::Malformed(k, _)) if k == "type"));
+    }
+
+    #[test]
+    pub fn option_contract() {
+        let provider = InMemoryProvider::from_json(r#"[
+            {"scrip": "NIFTY:NSE:O:30/06/2022:16000:CE", "type": "option", "exchange": "nse",
+             "underlying": "NIFTY", "expiry": "2022-06-30", "strike": 16000, "optionType": "ce",
+             "lotSize": 50, "tickSize": 0.05, "lowerCircuit": 0.05, "upperCircuit": 820.5,
+             "freezeQuantity": 1800}
+        ]"#).unwrap();
+        let metadata = provider.get("NIFTY:NSE:O:30/06/2022:16000:CE").unwrap();
+        assert_eq!(metadata.lot_size(), 50);
+        assert_eq!(metadata.price_band(), Some((0.05, 820.5)));
+        assert_eq!(metadata.freeze_quantity(), Some(1800));
+        match metadata {
+            MetaData::Option(o) => assert_eq!(o.expiry, NaiveDate::from_ymd_opt(2022, 6, 30)),
+            m => panic!("Unexpected option metadata: {:?}", m),
+        }
+    }
+
+    #[test]
+    pub fn fractional_quantities() {
+        let mut contract: ContractSpec = Default::default();
+        assert_eq!(contract.update("lotSize", &AttributeValue::N(String::from("25"))), Ok(true));
+        assert!(contract.update("lotSize", &AttributeValue::N(String::from("2.5"))).is_err());
+        assert!(contract.update("freezeQuantity", &AttributeValue::N(String::from("-1"))).is_err());
+        assert_eq!(contract.update("scrip", &AttributeValue::S(String::from("SBIN"))), Ok(false));
+        assert_eq!((contract.lot_size, contract.freeze_quantity), (25, None));
+    }
 }
@@ -0,0 +1,378 @@
+use crate::scrip::Scrip;
+use crate::stock::{StockScrip, IndexScrip};
+use crate::options::{OptionScrip, OptionType};
+use crate::options::EXPIRY_FORMAT;
+use crate::info::{MetaData, StockMetaData, IndexMetaData, OptionMetaData, FutureMetaData, ContractSpec};
+use crate::provider::{read_file, split_csv_line, MetadataProvider};
+use crate::redis_utils::RedisScrip;
+use crate::error::MetadataError;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+// =============================================================================
+//                              Instrument Master
+// =============================================================================
+
+// Kite trading symbols of indices, and the names we use for them.
+pub const INDEX_NAMES: [(&str, &str); 6] = [
+    ("NIFTY 50", "NIFTY"),
+    ("NIFTY BANK", "BANKNIFTY"),
+    ("NIFTY FIN SERVICE", "FINNIFTY"),
+    ("NIFTY MID SELECT", "MIDCPNIFTY"),
+    ("SENSEX", "SENSEX"),
+    ("BANKEX", "BANKEX"),
+];
+
+fn index_name(symbol: &str) -> Option<&'static str> {
+    INDEX_NAMES
+        .iter()
+        .find(|(kite, name)| *kite == symbol || *name == symbol)
+        .map(|(_, name)| *name)
+}
+
+// A row of the instruments dump. `scrip` is `None` for instruments we have no
+// scrip type for, such as futures and currency derivatives.
+#[derive(Clone, Debug)]
+pub struct Instrument {
+    pub instrument_token: u64,
+    pub exchange_token: u64,
+    pub trading_symbol: String,
+    pub name: String,
+    pub exchange: String,
+    pub segment: String,
+    pub instrument_type: String,
+    pub expiry: Option<NaiveDate>,
+    pub strike: Option<f64>,
+    pub tick_size: f64,
+    pub lot_size: u32,
+    pub scrip: Option<Scrip>,
+}
+
+impl Instrument {
+    // Columns as in Kite's instruments CSV.
+    fn from_row(row: &HashMap<&str, &str>) -> Result<Self, MetadataError> {
+        let column = |key: &str| row.get(key).copied().unwrap_or_default();
+        let number = |key: &str| column(key).parse::<f64>().map_err(|_| {
+            MetadataError::Malformed(key.to_string(), format!("invalid number {}", column(key)))
+        });
+        let count = |key: &str| match number(key)? {
+            x if x.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&x) => Ok(x as u32),
+            x => Err(MetadataError::Malformed(key.to_string(), format!("expected a whole quantity, found {}", x))),
+        };
+        let token = |key: &str| column(key).parse::<u64>().map_err(|_| {
+            MetadataError::Malformed(key.to_string(), format!("invalid token {}", column(key)))
+        });
+
+        let expiry = match column("expiry") {
+            "" => None,
+            date => Some(NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                MetadataError::Malformed(String::from("expiry"), format!("expected YYYY-MM-DD, found {}", date))
+            })?),
+        };
+        let strike = number("strike").ok().filter(|x| *x > 0.0);
+
+        let mut instrument = Self {
+            instrument_token: token("instrument_token")?,
+            exchange_token: token("exchange_token")?,
+            trading_symbol: column("tradingsymbol").to_string(),
+            name: column("name").to_string(),
+            exchange: column("exchange").to_string(),
+            segment: column("segment").to_string(),
+            instrument_type: column("instrument_type").to_string(),
+            expiry,
+            strike,
+            tick_size: number("tick_size")?,
+            lot_size: count("lot_size")?,
+            scrip: None,
+        };
+        instrument.scrip = instrument.to_scrip();
+        Ok(instrument)
+    }
+
+    // Derivative segments trade under the exchange of their underlying.
+    fn exchange_name(&self) -> Option<&'static str> {
+        match self.exchange.as_str() {
+            "NSE" | "NFO" => Some("NSE"),
+            "BSE" | "BFO" => Some("BSE"),
+            "MCX" => Some("MCX"),
+            _ => None,
+        }
+    }
+
+    fn to_scrip(&self) -> Option<Scrip> {
+        let exchange = self.exchange_name()?;
+
+        if self.segment == "INDICES" {
+            let name = index_name(&self.trading_symbol).unwrap_or(&self.trading_symbol);
+            return Some(Scrip::Index(IndexScrip::new(name, exchange, "I")));
+        }
+
+        let option_type = match self.instrument_type.as_str() {
+            "EQ" => return Some(Scrip::Stock(StockScrip::new(&self.trading_symbol, exchange, "C"))),
+            "CE" => OptionType::CE,
+            "PE" => OptionType::PE,
+            _ => return None,
+        };
+
+        // Keys hold whole strikes only.
+        let strike = self.strike.filter(|x| x.fract() == 0.0)?;
+        let underlying = match index_name(&self.name) {
+            Some(name) => Scrip::Index(IndexScrip::new(name, exchange, "I")),
+            None => Scrip::Stock(StockScrip::new(&self.name, exchange, "C")),
+        };
+        Some(Scrip::Option(OptionScrip::new(
+            &self.name, exchange, "O", self.expiry?, strike as u32, option_type, Some(underlying)
+        )))
+    }
+
+    // Name the metadata is stored under. Futures have no scrip, so they use a
+    // key in the same layout as options.
+    pub fn metadata_name(&self) -> Option<String> {
+        match (&self.scrip, self.instrument_type.as_str()) {
+            (Some(scrip), _) => Some(scrip.metadata_name()),
+            (None, "FUT") => Some(format!(
+                "{}:{}:F:{}", self.name, self.exchange_name()?, self.expiry?.format(&EXPIRY_FORMAT)
+            )),
+            _ => None,
+        }
+    }
+
+    fn to_metadata(&self) -> Option<MetaData> {
+        let exchange = self.exchange_name()?.to_string();
+        let currency = String::from("INR");
+        let timezone = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+        let contract = ContractSpec {
+            lot_size: self.lot_size,
+            tick_size: self.tick_size,
+            ..Default::default()
+        };
+
+        let metadata = match &self.scrip {
+            Some(Scrip::Index(i)) => MetaData::Index(IndexMetaData {
+                scrip: i.name.clone(),
+                open_time: None,
+                close_time: None,
+                currency,
+                exchange,
+                timezone,
+                constituents: HashMap::new(),
+            }),
+            Some(Scrip::Stock(s)) => MetaData::Stock(StockMetaData {
+                scrip: s.name.clone(),
+                open_time: None,
+                close_time: None,
+                currency,
+                exchange,
+                timezone,
+                free_float_market_cap: 0.0,
+                contract,
+            }),
+            Some(Scrip::Option(o)) => MetaData::Option(OptionMetaData {
+                scrip: o.key(),
+                underlying: self.name.clone(),
+                open_time: None,
+                close_time: None,
+                currency,
+                exchange,
+                timezone,
+                expiry: self.expiry,
+                strike: self.strike.unwrap_or_default(),
+                option_type: self.instrument_type.clone(),
+                contract,
+            }),
+            None => MetaData::Future(FutureMetaData {
+                scrip: self.metadata_name()?,
+                underlying: self.name.clone(),
+                open_time: None,
+                close_time: None,
+                currency,
+                exchange,
+                timezone,
+                expiry: self.expiry,
+                contract,
+            }),
+        };
+        Some(metadata)
+    }
+
+    // Existing metadata with the lot and tick size from the dump. `None` for
+    // indices, which have nothing to update.
+    fn update_metadata(&self, metadata: MetaData) -> Option<MetaData> {
+        let mut metadata = metadata;
+        let contract = metadata.contract_mut()?;
+        contract.lot_size = self.lot_size;
+        contract.tick_size = self.tick_size;
+        Some(metadata)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InstrumentMaster {
+    pub instruments: Vec<Instrument>,
+    // Rows that could not be parsed, by line number, and why.
+    pub rejected: Vec<(usize, MetadataError)>,
+    by_token: HashMap<u64, usize>,
+    by_symbol: HashMap<(String, String), usize>,
+    by_key: HashMap<String, usize>,
+}
+
+impl InstrumentMaster {
+    pub fn new(instruments: Vec<Instrument>) -> Self {
+        let mut master = Self::default();
+        instruments.into_iter().for_each(|x| master.add(x));
+        master
+    }
+
+    pub fn add(&mut self, instrument: Instrument) {
+        let idx = self.instruments.len();
+        self.by_token.insert(instrument.instrument_token, idx);
+        self.by_symbol.insert((instrument.exchange.clone(), instrument.trading_symbol.clone()), idx);
+        if let Some(scrip) = &instrument.scrip {
+            self.by_key.insert(scrip.key(), idx);
+        }
+        self.instruments.push(instrument);
+    }
+
+    // Kite's instruments dump, with a header row. Rows that fail to parse are
+    // skipped and listed in `rejected`.
+    pub fn from_csv(csv: &str) -> Result<Self, MetadataError> {
+        let mut lines = csv.lines().enumerate().filter(|(_, x)| !x.trim().is_empty());
+        let header: Vec<String> = match lines.next() {
+            Some((_, h)) => split_csv_line(h),
+            None => return Ok(Self::default()),
+        };
+
+        let mut instruments: Vec<Instrument> = Vec::new();
+        let mut rejected: Vec<(usize, MetadataError)> = Vec::new();
+        for (idx, line) in lines {
+            let fields = split_csv_line(line);
+            let row: HashMap<&str, &str> = header
+                .iter()
+                .map(|x| x.as_str())
+                .zip(fields.iter().map(|x| x.as_str()))
+                .collect();
+            match Instrument::from_row(&row) {
+                Ok(instrument) => instruments.push(instrument),
+                Err(e) => rejected.push((idx + 1, e)),
+            }
+        }
+        Ok(Self { rejected, ..Self::new(instruments) })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, MetadataError> {
+        Self::from_csv(&read_file(path)?)
+    }
+
+    pub fn by_token(&self, token: u64) -> Option<&Instrument> {
+        self.by_token.get(&token).map(|x| &self.instruments[*x])
+    }
+
+    // Exchange as in the dump, e.g. NFO for NSE options.
+    pub fn by_symbol(&self, exchange: &str, symbol: &str) -> Option<&Instrument> {
+        self.by_symbol
+            .get(&(exchange.to_string(), symbol.to_string()))
+            .map(|x| &self.instruments[*x])
+    }
+
+    pub fn by_key(&self, key: &str) -> Option<&Instrument> {
+        self.by_key.get(key).map(|x| &self.instruments[*x])
+    }
+
+    pub fn scrips(&self) -> Vec<Scrip> {
+        self.instruments.iter().filter_map(|x| x.scrip.clone()).collect()
+    }
+
+    // Adds metadata for instruments the provider does not know of yet, and
+    // updates the lot and tick size of the ones it does. Returns the number of
+    // entries written.
+    pub fn store(&self, provider: &dyn MetadataProvider) -> Result<usize, MetadataError> {
+        let mut written = 0;
+        for instrument in self.instruments.iter() {
+            let name = match instrument.metadata_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let metadata = match provider.get(&name) {
+                Ok(existing) => instrument.update_metadata(existing),
+                Err(MetadataError::NotFound(_)) => instrument.to_metadata(),
+                Err(e) => return Err(e),
+            };
+            if let Some(metadata) = metadata {
+                provider.put(&name, metadata)?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Scrip;
+    use crate::redis_utils::RedisScrip;
+    use crate::provider::{InMemoryProvider, MetadataProvider};
+    use crate::info::MetaData;
+    use crate::instruments::*;
+
+    const INSTRUMENTS: &str = "instrument_token,exchange_token,tradingsymbol,name,last_price,expiry,strike,tick_size,lot_size,instrument_type,segment,exchange
+        256265,1001,NIFTY 50,NIFTY 50,0,,0,0,0,EQ,INDICES,NSE
+        779521,3045,SBIN,STATE BANK OF INDIA,0,,0,0.05,1,EQ,NSE,NSE
+        12345026,48223,NIFTY22JUN16000CE,NIFTY,0,2022-06-30,16000,0.05,50,CE,NFO-OPT,NFO
+        12345282,48224,NIFTY22JUNFUT,NIFTY,0,2022-06-30,0,0.05,50,FUT,NFO-FUT,NFO
+        412675,1612,USDINR22JUNFUT,USDINR,0,2022-06-28,0,0.0025,1,FUT,CDS-FUT,CDS";
+
+    #[test]
+    fn parse_instruments() {
+        let master = InstrumentMaster::from_csv(INSTRUMENTS).unwrap();
+        assert_eq!(master.instruments.len(), 5);
+        assert_eq!(master.scrips().len(), 3);
+
+        let nifty = master.by_token(256265).unwrap();
+        assert_eq!(nifty.scrip.as_ref().map(|x| x.key()), Some(String::from("NIFTY:NSE:I")));
+
+        let call = master.by_symbol("NFO", "NIFTY22JUN16000CE").unwrap();
+        assert_eq!(call.lot_size, 50);
+        match &call.scrip {
+            Some(Scrip::Option(o)) => {
+                assert_eq!(o.strike, 16000);
+                assert!(matches!(o.underlying.as_deref(), Some(Scrip::Index(_))));
+            },
+            scrip => panic!("Unexpected scrip: {:?}", scrip),
+        }
+        assert_eq!(master.by_key(&call.scrip.as_ref().unwrap().key()).unwrap().instrument_token, 12345026);
+        assert!(master.by_token(12345282).unwrap().scrip.is_none());
+        assert!(master.rejected.is_empty());
+    }
+
+    #[test]
+    fn quoted_and_bad_rows() {
+        let csv = "instrument_token,exchange_token,tradingsymbol,name,last_price,expiry,strike,tick_size,lot_size,instrument_type,segment,exchange
+            779521,3045,SBIN,\"STATE BANK, OF INDIA\",0,,0,0.05,1,EQ,NSE,NSE
+            bad,3046,INFY,INFOSYS,0,,0,0.05,1,EQ,NSE,NSE
+            1270529,4963,ICICIBANK,ICICI BANK,0,,0,0.05,2.5,EQ,NSE,NSE
+            2714625,10604,BHARTIARTL,BHARTI AIRTEL,0,,0,0.05,-1,EQ,NSE,NSE";
+        let master = InstrumentMaster::from_csv(csv).unwrap();
+        assert_eq!(master.instruments.len(), 1);
+        assert_eq!(master.by_token(779521).unwrap().name, "STATE BANK, OF INDIA");
+        assert_eq!(master.rejected.len(), 3);
+        assert!(master.rejected.iter().all(|x| matches!(x.1, MetadataError::Malformed(..))));
+        assert_eq!(master.rejected.iter().map(|x| x.0).collect::<Vec<usize>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn store_metadata() {
+        let master = InstrumentMaster::from_csv(INSTRUMENTS).unwrap();
+        let provider = InMemoryProvider::new();
+        assert_eq!(master.store(&provider), Ok(4));
+        // Everything but the index is updated again.
+        assert_eq!(master.store(&provider), Ok(3));
+        assert!(matches!(provider.get("SBIN"), Ok(MetaData::Stock(_))));
+        assert_eq!(provider.get("NIFTY:NSE:F:30/06/2022").map(|x| x.lot_size()), Ok(50));
+        assert_eq!(provider.get("NIFTY:NSE:O:30/06/2022:16000:CE").map(|x| x.lot_size()), Ok(50));
+    }
+}
@@ -6,6 +6,7 @@ extern crate r2d2;
 extern crate redis;
 extern crate thiserror;
 extern crate serde;
+extern crate serde_json;
 extern crate aws_sdk_dynamodb;
 extern crate aws_config;
 extern crate aws_types;
@@ -17,21 +18,59 @@ pub mod scrip;
 pub mod tickers;
 pub mod stock;
 pub mod options;
+pub mod pricing;
 pub mod orders;
+pub mod risk;
+pub mod algo;
 pub mod position;
+pub mod lots;
+pub mod account;
+pub mod exposure;
+pub mod settlement;
+pub mod mtm;
+pub mod var;
+pub mod monitor;
+pub mod report;
 pub mod live_candle;
 pub mod utils;
 pub mod redis_utils;
 pub mod info;
+pub mod metadata_client;
+pub mod provider;
+pub mod session;
+pub mod calendar;
+pub mod expiry;
+pub mod instruments;
+pub mod index;
+pub mod subscription;
+pub mod writer;
 
 pub use scrip::*;
 pub use tickers::*;
 pub use stock::*;
 pub use options::*;
+pub use pricing::*;
 pub use position::*;
+pub use lots::*;
+pub use account::*;
+pub use exposure::*;
+pub use settlement::*;
+pub use mtm::*;
+pub use var::*;
+pub use monitor::*;
+pub use report::*;
 pub use orders::*;
+pub use risk::*;
+pub use algo::*;
 pub use live_candle::*;
 pub use redis_utils::*;
+pub use session::*;
+pub use calendar::*;
+pub use expiry::*;
+pub use instruments::*;
+pub use index::*;
+pub use subscription::*;
+pub use writer::*;
 
 #[cfg(test)]
 mod test_util;
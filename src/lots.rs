@@ -0,0 +1,207 @@
+use crate::scrip::Scrip;
+use crate::position::{Transaction, scrip_map};
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::{HashMap, VecDeque};
+use serde::{Serialize, Deserialize};
+
+// =============================================================================
+//                              Lot Level Tracking
+// =============================================================================
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LotPolicy {
+    #[default]
+    Fifo,
+    Lifo,
+    // Closes lots in FIFO order (for holding period) but books the gain
+    // against the average cost. Open lots are repriced to the running average
+    // cost whenever a lot is added.
+    Average,
+}
+
+// Quantity is signed; short lots are opened by sells when nothing is held.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lot {
+    pub quantity: i32,
+    pub price: f64,
+    pub open_time: DateTime<Local>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClosedLot {
+    pub scrip: Scrip,
+    // Signed quantity of the lot that was closed.
+    pub quantity: i32,
+    pub open_price: f64,
+    pub close_price: f64,
+    pub open_time: DateTime<Local>,
+    pub close_time: DateTime<Local>,
+    pub realized: f64,
+}
+
+impl ClosedLot {
+    pub fn holding_period(&self) -> Duration {
+        self.close_time - self.open_time
+    }
+
+    pub fn is_intraday(&self) -> bool {
+        self.open_time.date_naive() == self.close_time.date_naive()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LotBook {
+    pub policy: LotPolicy,
+    #[serde(with = "scrip_map")]
+    pub open: HashMap<Scrip, VecDeque<Lot>>,
+    pub closed: Vec<ClosedLot>,
+}
+
+impl LotBook {
+    pub fn new(policy: LotPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    // Closes the open lots on the opposite side of the transaction according
+    // to the policy and opens a new lot with the quantity left over.
+    pub fn add_transaction(&mut self, transaction: &Transaction) {
+        let mut remaining = transaction.quantity;
+        let lots = self.open.entry(transaction.scrip.clone()).or_default();
+
+        while remaining != 0 {
+            let lot = match self.policy {
+                LotPolicy::Lifo => lots.back_mut(),
+                LotPolicy::Fifo | LotPolicy::Average => lots.front_mut(),
+            };
+            let lot = match lot {
+                Some(l) if l.quantity.signum() != remaining.signum() => l,
+                _ => break,
+            };
+
+            let quantity = lot.quantity.abs().min(remaining.abs()) * lot.quantity.signum();
+            let open_price = lot.price;
+            self.closed.push(ClosedLot {
+                scrip: transaction.scrip.clone(),
+                quantity,
+                open_price,
+                close_price: transaction.avg_price,
+                open_time: lot.open_time,
+                close_time: transaction.exec_time,
+                realized: (quantity as f64) * (transaction.avg_price - open_price),
+            });
+
+            lot.quantity -= quantity;
+            remaining += quantity;
+            if lot.quantity == 0 {
+                match self.policy {
+                    LotPolicy::Lifo => lots.pop_back(),
+                    LotPolicy::Fifo | LotPolicy::Average => lots.pop_front(),
+                };
+            }
+        }
+
+        if remaining != 0 {
+            lots.push_back(Lot {
+                quantity: remaining,
+                price: transaction.avg_price,
+                open_time: transaction.exec_time,
+            });
+            if self.policy == LotPolicy::Average {
+                let average = Self::average_price(lots);
+                lots.iter_mut().for_each(|x| x.price = average);
+            }
+        }
+        if lots.is_empty() {
+            self.open.remove(&transaction.scrip);
+        }
+    }
+
+    pub fn open_lots(&self, scrip: &Scrip) -> Vec<Lot> {
+        self.open.get(scrip).map_or(Vec::new(), |x| x.iter().cloned().collect())
+    }
+
+    pub fn realized(&self) -> f64 {
+        self.closed.iter().map(|x| x.realized).sum()
+    }
+
+    fn average_price(lots: &VecDeque<Lot>) -> f64 {
+        let quantity: i32 = lots.iter().map(|x| x.quantity).sum();
+        if quantity == 0 {
+            return 0.0;
+        }
+        lots.iter().fold(0.0, |x, y| x + (y.quantity as f64) * y.price) / (quantity as f64)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::lots::*;
+    use chrono::{Local, TimeZone};
+
+    fn transaction(quantity: i32, avg_price: f64, day: u32) -> Transaction {
+        Transaction {
+            scrip: Scrip::Stock(StockScrip::new("TEST", "NSE", "C")),
+            quantity,
+            avg_price,
+            exec_time: Local.with_ymd_and_hms(2022, 6, day, 10, 0, 0).unwrap(),
+        }
+    }
+
+    fn book_with(policy: LotPolicy) -> LotBook {
+        let mut book = LotBook::new(policy);
+        book.add_transaction(&transaction(10, 100.0, 1));
+        book.add_transaction(&transaction(10, 120.0, 2));
+        book.add_transaction(&transaction(-15, 130.0, 3));
+        book
+    }
+
+    #[test]
+    fn fifo_lots() {
+        let book = book_with(LotPolicy::Fifo);
+        assert_eq!(book.realized(), 300.0 + 50.0);
+        assert_eq!(book.closed[0].holding_period(), Duration::days(2));
+        let open = book.open_lots(&transaction(0, 0.0, 1).scrip);
+        assert_eq!((open[0].quantity, open[0].price), (5, 120.0));
+    }
+
+    #[test]
+    fn lifo_lots() {
+        let book = book_with(LotPolicy::Lifo);
+        assert_eq!(book.realized(), 100.0 + 150.0);
+        let open = book.open_lots(&transaction(0, 0.0, 1).scrip);
+        assert_eq!((open[0].quantity, open[0].price), (5, 100.0));
+    }
+
+    #[test]
+    fn average_lots() {
+        let book = book_with(LotPolicy::Average);
+        assert_eq!(book.realized(), 15.0 * 20.0);
+    }
+
+    #[test]
+    fn average_lots_across_sells() {
+        let mut book = LotBook::new(LotPolicy::Average);
+        book.add_transaction(&transaction(10, 100.0, 1));
+        book.add_transaction(&transaction(10, 120.0, 2));
+        book.add_transaction(&transaction(-10, 130.0, 3));
+        book.add_transaction(&transaction(-10, 130.0, 4));
+        assert_eq!(book.realized(), 200.0 + 200.0);
+
+        // Adding to the remaining lot moves the average cost again.
+        book.add_transaction(&transaction(10, 100.0, 5));
+        book.add_transaction(&transaction(10, 140.0, 6));
+        book.add_transaction(&transaction(-5, 130.0, 7));
+        assert_eq!(book.realized(), 400.0 + 5.0 * 10.0);
+        let open = book.open_lots(&transaction(0, 0.0, 1).scrip);
+        assert!(open.iter().all(|x| x.price == 120.0));
+    }
+}
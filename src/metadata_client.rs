@@ -0,0 +1,260 @@
+use aws_sdk_dynamodb::model::{AttributeValue, KeysAndAttributes};
+use aws_sdk_dynamodb::{Client, Endpoint};
+use aws_types::region::Region;
+use crate::info::{MetaData, TABLE_NAME};
+use crate::error::MetadataError;
+use std::collections::HashMap;
+
+// DynamoDB accepts at most 100 keys per BatchGetItem request.
+const BATCH_SIZE: usize = 100;
+// Attempts at fetching the keys DynamoDB reports as unprocessed.
+const BATCH_RETRIES: u32 = 5;
+// Delay before the first retry, doubled on every attempt after.
+const BATCH_BACKOFF_MS: u64 = 50;
+
+fn transport<E: std::fmt::Display>(e: E) -> MetadataError {
+    MetadataError::Transport(e.to_string())
+}
+
+fn scrip_key(name: &str) -> HashMap<String, AttributeValue> {
+    HashMap::from([(String::from("scrip"), AttributeValue::S(name.to_string()))])
+}
+
+// Metadata keyed by scrip name. Items that cannot be parsed are skipped and
+// reported with their scrip name, so that one bad item does not fail the
+// whole batch.
+#[derive(Clone, Debug, Default)]
+pub struct BatchMetadata {
+    pub metadata: HashMap<String, MetaData>,
+    pub rejected: Vec<(String, MetadataError)>,
+}
+
+impl BatchMetadata {
+    fn add_items(&mut self, items: &[HashMap<String, AttributeValue>]) {
+        for item in items.iter() {
+            let name = match item.get("scrip") {
+                Some(AttributeValue::S(name)) => name.to_string(),
+                _ => continue,
+            };
+            match MetaData::from_response(item) {
+                Ok(parsed) => { self.metadata.insert(name, parsed); },
+                Err(e) => self.rejected.push((name, e)),
+            }
+        }
+    }
+}
+
+// =============================================================================
+//                            Async Metadata Client
+// =============================================================================
+
+// Wraps a single DynamoDB client, meant to be created once and shared.
+#[derive(Clone, Debug)]
+pub struct MetadataClient {
+    pub client: Client,
+    pub table: String,
+}
+
+impl MetadataClient {
+    // Client for `TABLE_NAME` configured from the environment.
+    pub async fn new() -> Self {
+        let shared_config = aws_config::load_from_env().await;
+        Self::from_client(Client::new(&shared_config), TABLE_NAME)
+    }
+
+    // Client for `table`, optionally against another region or endpoint (such
+    // as DynamoDB Local on "http://localhost:8000").
+    pub async fn from_config(
+        table: &str,
+        endpoint: Option<&str>,
+        region: Option<&str>,
+    ) -> Result<Self, MetadataError> {
+        let mut loader = aws_config::from_env();
+        if let Some(region) = region {
+            loader = loader.region(Region::new(region.to_string()));
+        }
+        let shared_config = loader.load().await;
+
+        let mut config = aws_sdk_dynamodb::config::Builder::from(&shared_config);
+        if let Some(endpoint) = endpoint {
+            let uri: http::Uri = endpoint.parse().map_err(transport)?;
+            config = config.endpoint_resolver(Endpoint::immutable(uri));
+        }
+        Ok(Self::from_client(Client::from_conf(config.build()), table))
+    }
+
+    pub fn from_client(client: Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    pub async fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        let response = self.client
+            .get_item()
+            .table_name(&self.table)
+            .key("scrip", AttributeValue::S(name.to_string()))
+            .send()
+            .await
+            .map_err(transport)?;
+
+        match response.item() {
+            Some(item) => MetaData::from_response(item),
+            None => Err(MetadataError::NotFound(name.to_string())),
+        }
+    }
+
+    // Writes `metadata` under `name`, replacing any existing item.
+    pub async fn put(&self, name: &str, metadata: &MetaData) -> Result<(), MetadataError> {
+        let mut item = metadata.to_item();
+        item.insert(String::from("scrip"), AttributeValue::S(name.to_string()));
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(transport)?;
+        Ok(())
+    }
+
+    // Names missing from the table are left out of the result.
+    pub async fn batch_get(&self, names: &[String]) -> Result<BatchMetadata, MetadataError> {
+        let mut batch: BatchMetadata = Default::default();
+
+        for chunk in names.chunks(BATCH_SIZE) {
+            let keys = chunk.iter().map(|x| scrip_key(x)).collect();
+            let mut pending = Some(KeysAndAttributes::builder().set_keys(Some(keys)).build());
+
+            for attempt in 0..BATCH_RETRIES {
+                let request = match pending.take() {
+                    Some(r) => r,
+                    None => break,
+                };
+                if attempt > 0 {
+                    let backoff = BATCH_BACKOFF_MS << (attempt - 1);
+                    tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+                }
+                let response = self.client
+                    .batch_get_item()
+                    .request_items(&self.table, request)
+                    .send()
+                    .await
+                    .map_err(transport)?;
+
+                if let Some(items) = response.responses().and_then(|x| x.get(&self.table)) {
+                    batch.add_items(items);
+                }
+
+                pending = response
+                    .unprocessed_keys()
+                    .and_then(|x| x.get(&self.table))
+                    .filter(|x| matches!(x.keys(), Some(k) if !k.is_empty()))
+                    .cloned();
+            }
+
+            if pending.is_some() {
+                return Err(MetadataError::Transport(String::from("Unprocessed keys left after retries")));
+            }
+        }
+
+        Ok(batch)
+    }
+}
+
+// =============================================================================
+//                           Blocking Metadata Client
+// =============================================================================
+
+// For synchronous callers. Creating or calling it from within a tokio runtime
+// returns a transport error instead of blocking the runtime; use
+// `MetadataClient` there.
+pub struct BlockingMetadataClient {
+    runtime: tokio::runtime::Runtime,
+    pub inner: MetadataClient,
+}
+
+impl BlockingMetadataClient {
+    pub fn new() -> Result<Self, MetadataError> {
+        Self::outside_runtime()?;
+        let runtime = Self::runtime()?;
+        let inner = runtime.block_on(MetadataClient::new());
+        Ok(Self { runtime, inner })
+    }
+
+    pub fn from_config(table: &str, endpoint: Option<&str>, region: Option<&str>) -> Result<Self, MetadataError> {
+        Self::outside_runtime()?;
+        let runtime = Self::runtime()?;
+        let inner = runtime.block_on(MetadataClient::from_config(table, endpoint, region))?;
+        Ok(Self { runtime, inner })
+    }
+
+    fn runtime() -> Result<tokio::runtime::Runtime, MetadataError> {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(transport)
+    }
+
+    fn outside_runtime() -> Result<(), MetadataError> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(_) => Err(MetadataError::Transport(String::from(
+                "Blocking metadata client used inside an async runtime"
+            ))),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn block_on<F: std::future::Future>(&self, future: F) -> Result<F::Output, MetadataError> {
+        Self::outside_runtime()?;
+        Ok(self.runtime.block_on(future))
+    }
+
+    pub fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        self.block_on(self.inner.get(name))?
+    }
+
+    pub fn batch_get(&self, names: &[String]) -> Result<BatchMetadata, MetadataError> {
+        self.block_on(self.inner.batch_get(names))?
+    }
+
+    pub fn put(&self, name: &str, metadata: &MetaData) -> Result<(), MetadataError> {
+        self.block_on(self.inner.put(name, metadata))?
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata_client::*;
+
+    #[tokio::test]
+    async fn blocking_client_inside_runtime() {
+        assert!(matches!(BlockingMetadataClient::new(), Err(MetadataError::Transport(_))));
+        let client = BlockingMetadataClient::from_config(TABLE_NAME, None, None);
+        assert!(matches!(client, Err(MetadataError::Transport(_))));
+    }
+
+    #[test]
+    fn bad_items_skipped() {
+        let item = |fields: &[(&str, AttributeValue)]| -> HashMap<String, AttributeValue> {
+            fields.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+        };
+        let s = |x: &str| AttributeValue::S(x.to_string());
+        let items = vec![
+            item(&[("scrip", s("SBIN")), ("type", s("cash")), ("exchange", s("nse"))]),
+            item(&[("scrip", s("INFY")), ("type", s("cash")), ("lotSize", AttributeValue::N(String::from("2.5")))]),
+        ];
+
+        let mut batch: BatchMetadata = Default::default();
+        batch.add_items(&items);
+        assert!(matches!(batch.metadata.get("SBIN"), Some(MetaData::Stock(_))));
+        assert_eq!(batch.rejected.len(), 1);
+        assert!(matches!(&batch.rejected[0], (name, MetadataError::Malformed(..)) if name == "INFY"));
+    }
+}
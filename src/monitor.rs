@@ -0,0 +1,266 @@
+use crate::scrip::Scrip;
+use crate::position::Position;
+use crate::orders::{Order, OrderType};
+use crate::redis_utils::fetch_tickers;
+use crate::info::MetaData;
+use crate::error::Error;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// =============================================================================
+//                          Stop Loss and Target Monitor
+// =============================================================================
+
+// Price levels at which a holding is exited. For short holdings the stop is
+// above and the target below the market. `trailing` is the distance from the
+// best of the average price and the prices seen since the monitor started.
+#[derive(Clone, Debug, Default)]
+pub struct ExitRule {
+    pub stop: Option<f64>,
+    pub target: Option<f64>,
+    pub trailing: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RiskMonitor {
+    pub rules: HashMap<Scrip, ExitRule>,
+    // Loss (as a positive number) on `Position::get_pnl` that exits everything.
+    pub portfolio_stop: Option<f64>,
+    // Time of day, in `timezone`, at which everything is squared off.
+    pub square_off: Option<NaiveTime>,
+    pub timezone: FixedOffset,
+    best_prices: HashMap<Scrip, f64>,
+}
+
+impl Default for RiskMonitor {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::new(),
+            portfolio_stop: None,
+            square_off: None,
+            timezone: FixedOffset::east_opt(0).unwrap(),
+            best_prices: HashMap::new(),
+        }
+    }
+}
+
+impl RiskMonitor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Square off `before_close` ahead of the exchange close time in the
+    // metadata, e.g. 15 minutes for 15:15 on NSE.
+    pub fn from_metadata(metadata: &MetaData, before_close: Duration) -> Self {
+        Self {
+            square_off: metadata.close_time().map(|t| t - before_close),
+            timezone: metadata.timezone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_rule(&mut self, scrip: Scrip, rule: ExitRule) -> &mut Self {
+        self.best_prices.remove(&scrip);
+        self.rules.insert(scrip, rule);
+        self
+    }
+
+    fn exit_order(scrip: &Scrip, quantity: i32) -> Order {
+        Order::new(scrip.clone(), -quantity, OrderType::MarketOrder)
+    }
+
+    fn exit_all(position: &Position) -> Vec<Order> {
+        position.holding
+            .iter()
+            .map(|(s, (q, _))| Self::exit_order(s, *q))
+            .collect()
+    }
+
+    pub fn is_square_off_time(&self, now: DateTime<Utc>) -> bool {
+        match self.square_off {
+            Some(t) => now.with_timezone(&self.timezone).time() >= t,
+            None => false,
+        }
+    }
+
+    // Exit orders for the holdings whose stop, target or trailing stop was hit.
+    // Every holding is exited on the portfolio stop or at square off time.
+    pub fn check(&mut self, position: &Position, now: DateTime<Utc>) -> Result<Vec<Order>, Error> {
+        if self.is_square_off_time(now) {
+            return Ok(Self::exit_all(position));
+        }
+        if let Some(stop) = self.portfolio_stop {
+            if position.get_pnl()? < -stop {
+                return Ok(Self::exit_all(position));
+            }
+        }
+
+        let scrips: Vec<Scrip> = position.holding.keys().filter(|x| self.rules.contains_key(x)).cloned().collect();
+        let tickers = fetch_tickers(&scrips)?;
+
+        let mut orders: Vec<Order> = Vec::new();
+        for (scrip, (quantity, avg_price)) in position.holding.iter() {
+            let (rule, ltp) = match (self.rules.get(scrip), tickers.get(scrip)) {
+                (Some(r), Some(t)) => (r, t.ltp),
+                _ => continue,
+            };
+            let long = *quantity > 0;
+
+            let best = self.best_prices.entry(scrip.clone()).or_insert(*avg_price);
+            *best = match long {
+                true => best.max(ltp),
+                false => best.min(ltp),
+            };
+            let trailing_level = rule.trailing.map(|d| if long { *best - d } else { *best + d });
+
+            let crossed = |level: Option<f64>, from_below: bool| match level {
+                Some(l) if from_below => ltp >= l,
+                Some(l) => ltp <= l,
+                None => false,
+            };
+            let stopped = crossed(rule.stop, !long);
+            let targeted = crossed(rule.target, long);
+            let trailed = crossed(trailing_level, !long);
+
+            if stopped || targeted || trailed {
+                orders.push(Self::exit_order(scrip, *quantity));
+            }
+        }
+        Ok(orders)
+    }
+
+    // Checks the position every `interval` and executes the exit orders until
+    // the position is flat or `stop` is set. A failed exit is handed to
+    // `on_error` and its rule stays armed, so it is retried on the next check.
+    // Failed checks are handed over without an order and retried the same way.
+    pub fn run<F>(&mut self, position: &RwLock<Position>, interval: Duration, stop: &AtomicBool, mut on_error: F)
+    where
+        F: FnMut(Option<&Order>, Error),
+    {
+        let interval = interval.to_std().unwrap_or_default();
+        while !stop.load(Ordering::Relaxed) {
+            // Checked on a copy so that fills are not blocked on the reads.
+            let current = position.read().unwrap().clone();
+            let checked = self.check(&current, Utc::now());
+            let orders = match checked {
+                Ok(orders) => orders,
+                Err(e) => {
+                    on_error(None, e);
+                    std::thread::sleep(interval);
+                    continue;
+                }
+            };
+            for order in orders.iter() {
+                // Exits reduce risk, so a breached loss limit must not block them.
+                match order.execute_unchecked() {
+                    Ok(transaction) => {
+                        self.rules.remove(&order.scrip);
+                        self.best_prices.remove(&order.scrip);
+                        position.write().unwrap().add_transaction(transaction);
+                    }
+                    Err(e) => on_error(Some(order), e),
+                }
+            }
+
+            if position.read().unwrap().holding.is_empty() {
+                break;
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::monitor::*;
+    use chrono::{Duration, NaiveTime, TimeZone, Utc};
+    use std::sync::RwLock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn test_scrip() -> Scrip {
+        Scrip::Stock(StockScrip::new("TEST", "NSE", "C"))
+    }
+
+    fn morning() -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(2022, 6, 1, 4, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn stop_and_target() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 10, 390.0);
+
+        let mut monitor = RiskMonitor::new();
+        monitor.add_rule(test_scrip(), ExitRule { target: Some(420.0), ..Default::default() });
+        assert!(monitor.check(&position, morning()).unwrap().is_empty());
+
+        monitor.add_rule(test_scrip(), ExitRule { stop: Some(401.0), ..Default::default() });
+        let orders = monitor.check(&position, morning()).unwrap();
+        assert_eq!(orders[0].quantity, -10);
+    }
+
+    #[test]
+    fn short_trailing_stop() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), -10, 410.0);
+
+        let mut monitor = RiskMonitor::new();
+        monitor.add_rule(test_scrip(), ExitRule { trailing: Some(1.0), ..Default::default() });
+        monitor.best_prices.insert(test_scrip(), 399.0);
+        let orders = monitor.check(&position, morning()).unwrap();
+        assert_eq!(orders[0].quantity, 10);
+    }
+
+    #[test]
+    fn trailing_from_average_price() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 10, 410.0);
+
+        let mut monitor = RiskMonitor::new();
+        monitor.add_rule(test_scrip(), ExitRule { trailing: Some(5.0), ..Default::default() });
+        let orders = monitor.check(&position, morning()).unwrap();
+        assert_eq!(orders[0].quantity, -10);
+    }
+
+    #[test]
+    fn failed_exit_stays_armed() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 1_000_000, 390.0);
+        let position = RwLock::new(position);
+
+        let mut monitor = RiskMonitor::new();
+        monitor.add_rule(test_scrip(), ExitRule { stop: Some(401.0), ..Default::default() });
+        let stop = AtomicBool::new(false);
+        let mut failures = 0;
+        monitor.run(&position, Duration::zero(), &stop, |_, _| {
+            failures += 1;
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(failures, 1);
+        assert!(monitor.rules.contains_key(&test_scrip()));
+        assert_eq!(position.read().unwrap().holding.len(), 1);
+    }
+
+    #[test]
+    fn square_off() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 10, 390.0);
+
+        let mut monitor = RiskMonitor {
+            square_off: Some(NaiveTime::from_hms_opt(15, 15, 0).unwrap()),
+            timezone: chrono::FixedOffset::east_opt(19800).unwrap(),
+            ..Default::default()
+        };
+        assert!(monitor.check(&position, morning()).unwrap().is_empty());
+        let orders = monitor.check(&position, Utc.with_ymd_and_hms(2022, 6, 1, 9, 45, 0).unwrap()).unwrap();
+        assert_eq!(orders.len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::scrip::Scrip;
+use crate::position::Position;
+use crate::account::{Account, json_error};
+use crate::redis_utils::{RedisScrip, fetch_tickers};
+use crate::utils::{pooled_connection, StopSignal};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// =============================================================================
+//                           Mark to Market Snapshots
+// =============================================================================
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtmSnapshot {
+    pub timestamp: DateTime<Utc>,
+    // Realized plus unrealized P&L, keyed by the scrip's key.
+    pub per_scrip: HashMap<String, f64>,
+    pub total: f64,
+}
+
+impl MtmSnapshot {
+    pub fn from_position(position: &Position, timestamp: DateTime<Utc>) -> redis::RedisResult<Self> {
+        let per_scrip: HashMap<String, f64> = position
+            .scrip_pnl()?
+            .into_iter()
+            .map(|(s, p)| (s.key(), p))
+            .collect();
+        let total = per_scrip.values().sum();
+        Ok(Self { timestamp, per_scrip, total })
+    }
+}
+
+impl Position {
+    pub fn scrip_pnl(&self) -> redis::RedisResult<HashMap<Scrip, f64>> {
+        let scrips: Vec<Scrip> = self.holding.keys().cloned().collect();
+        let tickers = fetch_tickers(&scrips)?;
+        let mut pnl: HashMap<Scrip, f64> = self.realized.clone();
+        self.holding.iter().for_each(|(s, (q, p))| {
+            if let Some(ticker) = tickers.get(s) {
+                *pnl.entry(s.clone()).or_insert(0.0) += (*q as f64) * (ticker.ltp - p);
+            }
+        });
+        Ok(pnl)
+    }
+}
+
+// =============================================================================
+//                                Equity Curve
+// =============================================================================
+
+#[derive(Clone, Debug, Default)]
+pub struct EquityCurve {
+    pub points: Vec<(DateTime<Utc>, f64)>,
+}
+
+impl EquityCurve {
+    pub fn from_snapshots(snapshots: &[MtmSnapshot]) -> Self {
+        let mut points: Vec<(DateTime<Utc>, f64)> = snapshots
+            .iter()
+            .map(|x| (x.timestamp, x.total))
+            .collect();
+        points.sort_by_key(|x| x.0);
+        Self { points }
+    }
+
+    // Distance below the running peak at every point (zero or negative).
+    pub fn drawdown(&self) -> Vec<(DateTime<Utc>, f64)> {
+        let mut peak = f64::NEG_INFINITY;
+        self.points
+            .iter()
+            .map(|(ts, pnl)| {
+                peak = peak.max(*pnl);
+                (*ts, pnl - peak)
+            })
+            .collect()
+    }
+
+    // Deepest drawdown as a positive number.
+    pub fn max_drawdown(&self) -> f64 {
+        -self.drawdown().iter().fold(0.0, |x, (_, d)| d.min(x))
+    }
+
+    pub fn high(&self) -> Option<f64> {
+        self.points.iter().map(|x| x.1).reduce(f64::max)
+    }
+
+    pub fn low(&self) -> Option<f64> {
+        self.points.iter().map(|x| x.1).reduce(f64::min)
+    }
+
+    // Points on the given day, in UTC.
+    pub fn for_day(&self, day: NaiveDate) -> Self {
+        let points = self.points
+            .iter()
+            .filter(|x| x.0.date_naive() == day)
+            .copied()
+            .collect();
+        Self { points }
+    }
+}
+
+// =============================================================================
+//                             Storage and Snapshotter
+// =============================================================================
+// ACCOUNT:<id>:MTM -> Sorted set of JSON snapshots scored by epoch millis
+
+impl Account {
+    pub fn mtm_key(&self) -> String {
+        format!("{}:MTM", self.key())
+    }
+
+    pub fn record_mtm(&self, snapshot: &MtmSnapshot) -> redis::RedisResult<()> {
+        let mut connection = pooled_connection()?;
+        let value = serde_json::to_string(snapshot).map_err(json_error)?;
+        redis::Cmd::zadd(self.mtm_key(), value, snapshot.timestamp.timestamp_millis())
+            .query(&mut *connection)
+    }
+
+    pub fn mtm_history(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> redis::RedisResult<Vec<MtmSnapshot>> {
+        let mut connection = pooled_connection()?;
+        let values: Vec<String> = redis::Cmd::zrangebyscore(
+            self.mtm_key(),
+            from.timestamp_millis(),
+            to.timestamp_millis(),
+        ).query(&mut *connection)?;
+
+        values
+            .into_iter()
+            .map(|v| serde_json::from_str(&v).map_err(json_error))
+            .collect()
+    }
+
+    // Drops snapshots taken before `before`.
+    pub fn trim_mtm(&self, before: DateTime<Utc>) -> redis::RedisResult<()> {
+        let mut connection = pooled_connection()?;
+        redis::Cmd::zrembyscore(self.mtm_key(), "-inf", format!("({}", before.timestamp_millis()))
+            .query(&mut *connection)
+    }
+
+    pub fn equity_curve(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> redis::RedisResult<EquityCurve> {
+        Ok(EquityCurve::from_snapshots(&self.mtm_history(from, to)?))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MtmSnapshotter {
+    pub account: Account,
+    pub interval: Duration,
+    // Snapshots older than this are trimmed after every snapshot.
+    pub retention: Option<Duration>,
+}
+
+impl MtmSnapshotter {
+    pub fn new(account: Account, interval: Duration) -> Self {
+        Self { account, interval, retention: None }
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn snapshot(&self, position: &Position) -> redis::RedisResult<MtmSnapshot> {
+        let snapshot = MtmSnapshot::from_position(position, Utc::now())?;
+        self.account.record_mtm(&snapshot)?;
+        if let Some(retention) = self.retention {
+            self.account.trim_mtm(snapshot.timestamp - retention)?;
+        }
+        Ok(snapshot)
+    }
+
+    // Records a snapshot every `interval` until `stop` is set, which also
+    // cuts the wait for the next snapshot short. The position is cloned so
+    // the lock is not held while pricing. Failed snapshots are handed to
+    // `on_error` and the loop carries on.
+    pub fn run<F>(&self, position: &RwLock<Position>, stop: &StopSignal, mut on_error: F)
+    where
+        F: FnMut(redis::RedisError),
+    {
+        let interval = self.interval.to_std().unwrap_or_default();
+        while !stop.is_set() {
+            let current = position.read().unwrap().clone();
+            if let Err(e) = self.snapshot(&current) {
+                on_error(e);
+            }
+            if stop.wait(interval) {
+                break;
+            }
+        }
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::mtm::*;
+    use chrono::{TimeZone, Utc};
+
+    fn curve(values: &[f64]) -> EquityCurve {
+        let points = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Utc.with_ymd_and_hms(2022, 6, 1, 4, i as u32, 0).unwrap(), *v))
+            .collect();
+        EquityCurve { points }
+    }
+
+    #[test]
+    fn max_drawdown() {
+        let curve = curve(&[0.0, 100.0, 40.0, 120.0, -30.0, 10.0]);
+        assert_eq!(curve.max_drawdown(), 150.0);
+        assert_eq!(curve.high(), Some(120.0));
+        assert_eq!(curve.low(), Some(-30.0));
+    }
+
+    #[test]
+    fn no_drawdown() {
+        let curve = curve(&[-10.0, 0.0, 10.0]);
+        assert_eq!(curve.max_drawdown(), 0.0);
+    }
+}
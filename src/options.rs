@@ -1,13 +1,15 @@
-use crate::{redis_utils::RedisScrip, scrip::Scrip, utils::POOL};
+use crate::{redis_utils::{RedisScrip, fetch_tickers}, scrip::Scrip, utils::POOL};
+use crate::tickers::Ticker;
 use crate::scrip::{Exchange, ExchangeType};
 use chrono::prelude::*;
 use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
 
 lazy_static::lazy_static! {
     pub static ref EXPIRY_FORMAT: String = String::from("%d/%m/%Y");
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OptionType {
     CE,
     PE,
@@ -23,7 +25,7 @@ impl std::fmt::Display for OptionType {
 //                      Single Option Ticker @ Strike, CE/PE
 // =============================================================================
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OptionScrip {
     pub name: String,
     pub exchange: Exchange,
@@ -158,14 +160,24 @@ impl OptionChain {
         self
     }
 
-    // pub fn reload(&mut self) {
-    //     self.calls.values_mut().for_each(|c| {
-    //         c.reload();
-    //     });
-    //     self.puts.values_mut().for_each(|p| {
-    //         p.reload();
-    //     });
-    // }
+    // Tickers of the calls and the puts by strike, fetched in a single round
+    // trip.
+    pub fn reload(&self) -> redis::RedisResult<(HashMap<u32, Ticker>, HashMap<u32, Ticker>)> {
+        let scrips: Vec<Scrip> = self.calls
+            .values()
+            .chain(self.puts.values())
+            .map(|x| Scrip::Option(x.clone()))
+            .collect();
+        let tickers = fetch_tickers(&scrips)?;
+
+        let by_strike = |options: &HashMap<u32, OptionScrip>| -> HashMap<u32, Ticker> {
+            options
+                .iter()
+                .filter_map(|(strike, x)| Some((*strike, tickers.get(&Scrip::Option(x.clone()))?.clone())))
+                .collect()
+        };
+        Ok((by_strike(&self.calls), by_strike(&self.puts)))
+    }
 
     // Update the Strikes in `calls` and `puts` according to Redis
     // Adds appropriate `OptionTickers` in calls and puts for strikeprices
@@ -178,50 +190,69 @@ impl OptionChain {
                 .parse::<u32>()
                 .unwrap()
         }) {
-            match self.calls.get_mut(&strike) {
-                Some(_) => (),
-                None => {
-                    let opt_scrip = OptionScrip {
-                        name: self.scrip.name.clone(),
-                        exchange: self.scrip.exchange,
-                        exchange_type: self.scrip.exchange_type,
-                        expiry : self.scrip.expiry,
-                        strike,
-                        option_type: OptionType::PE,
-                        underlying: self.scrip.underlying.clone().map(Box::new),
-                    };
-                    
-                    self.calls.insert(
-                        strike,
-                        opt_scrip,
-                    );
-                }
-            };
-            match self.puts.get_mut(&strike) {
-                Some(_) => (),
-                None => {
-                    let opt_scrip = OptionScrip {
-                        name: self.scrip.name.clone(),
-                        exchange: self.scrip.exchange,
-                        exchange_type: self.scrip.exchange_type,
-                        expiry : self.scrip.expiry,
-                        strike,
-                        option_type: OptionType::PE,
-                        underlying: self.scrip.underlying.clone().map(Box::new),
-                    };
-                    
-                    self.puts.insert(
-                        strike,
-                        opt_scrip,
-                    );
-                }
-            };
+            self.add_strike(strike);
         }
         self
     }
 
+    fn add_strike(&mut self, strike: u32) {
+        let option = |option_type| OptionScrip {
+            name: self.scrip.name.clone(),
+            exchange: self.scrip.exchange,
+            exchange_type: self.scrip.exchange_type,
+            expiry : self.scrip.expiry,
+            strike,
+            option_type,
+            underlying: self.scrip.underlying.clone().map(Box::new),
+        };
+        let call = option(OptionType::CE);
+        let put = option(OptionType::PE);
+
+        self.calls.entry(strike).or_insert(call);
+        self.puts.entry(strike).or_insert(put);
+    }
+
     pub fn sanity_check(&self) -> bool {
         // TODO
         true
     }
 }
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::options::*;
+    use crate::test_util::TEST_TICKER_1;
+
+    #[test]
+    fn new_strikes() {
+        let expiry = NaiveDate::from_ymd_opt(2022, 6, 30).unwrap();
+        let mut chain = OptionChain {
+            scrip: OptionChainScrip::new("TEST", "NSE", "O", expiry, None),
+            calls: HashMap::new(),
+            puts: HashMap::new(),
+        };
+        chain.add_strike(400);
+        assert!(matches!(chain.calls[&400].option_type, OptionType::CE));
+        assert!(matches!(chain.puts[&400].option_type, OptionType::PE));
+        assert_eq!(chain.strikes(), vec![400]);
+    }
+
+    #[test]
+    fn reload_chain() {
+        let expiry = NaiveDate::from_ymd_opt(2022, 6, 30).unwrap();
+        let option = |strike, option_type| OptionScrip::new("TEST", "NSE", "O", expiry, strike, option_type, None);
+        let chain = OptionChain {
+            scrip: OptionChainScrip::new("TEST", "NSE", "O", expiry, None),
+            calls: HashMap::from([(400, option(400, OptionType::CE)), (410, option(410, OptionType::CE))]),
+            puts: HashMap::from([(400, option(400, OptionType::PE)), (410, option(410, OptionType::PE))]),
+        };
+        let (calls, puts) = chain.reload().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(puts.len(), 2);
+        assert_eq!(calls[&410], *TEST_TICKER_1);
+    }
+}
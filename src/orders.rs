@@ -3,7 +3,11 @@ use crate::scrip::Scrip;
 use crate::redis_utils::RedisScrip;
 use crate::tickers::Ticker;
 use crate::error::Error;
+use crate::info::MetaData;
+use crate::risk::RiskGate;
+use crate::algo::{AlgoExecution, ExecutionAlgo};
 use chrono::prelude::*;
+use chrono::Duration;
 
 // =============================================================================
 //                                Single Order
@@ -87,7 +91,15 @@ impl Order {
         Ok(transaction)
     }
 
-    pub fn execute(&self) -> Result<Transaction, Error> {
+    // Checks the order against `gate` before executing it.
+    pub fn execute(&self, gate: &RiskGate, position: &Position) -> Result<Transaction, Error> {
+        gate.check_order(self, position)?;
+        self.execute_unchecked()
+    }
+
+    // Executes without any pre-trade checks. Only meant for orders that have
+    // already been checked, or that reduce risk such as exits and unwinds.
+    pub fn execute_unchecked(&self) -> Result<Transaction, Error> {
         // =======================================
         // Some subroutine to hand order to Broker
         // =======================================
@@ -100,6 +112,94 @@ impl Order {
             OrderType::LimitOrder(p) => Ok(p),
         }
     }
+
+    // Checks whether the order can be completely filled from the current
+    // depth. Limit orders only consider the levels at or better than the
+    // limit price.
+    pub fn fill_check(&self) -> Result<(), Error> {
+        let limit = match self.order_type {
+            OrderType::MarketOrder => return self.avg_price().map(|_| ()),
+            OrderType::LimitOrder(price) => price,
+        };
+
+        let ticker = self.scrip.updated_ticker();
+        let buy = self.quantity > 0;
+        let orders = match buy {
+            true => ticker.depth.ask,
+            false => ticker.depth.bid,
+        };
+        if orders.is_empty() { return Err(Error::EmptyDepth); }
+
+        let available = orders
+            .iter()
+            .filter(|x| match buy {
+                true => x.price <= limit,
+                false => x.price >= limit,
+            })
+            .fold(0, |y, x| y + (x.quantity as i32));
+        match self.quantity.abs() - available {
+            residual if residual > 0 => Err(Error::InsufficientDepth(residual)),
+            _ => Ok(()),
+        }
+    }
+
+    // Market order which squares off the given transaction.
+    pub fn reverse(transaction: &Transaction) -> Self {
+        Self::new(transaction.scrip.clone(), -transaction.quantity, OrderType::MarketOrder)
+    }
+
+    // Checks the order against the contract specification of its scrip. Only
+    // limit orders have a price to check against the tick and the circuit.
+    pub fn validate(&self, metadata: &MetaData) -> Result<(), Error> {
+        let quantity = self.quantity.unsigned_abs();
+        let lot_size = metadata.lot_size();
+        if !quantity.is_multiple_of(lot_size) {
+            return Err(Error::NotLotMultiple(quantity, lot_size));
+        }
+        if let Some(freeze) = metadata.freeze_quantity() {
+            if quantity > freeze {
+                return Err(Error::FreezeQuantityExceeded(quantity, freeze));
+            }
+        }
+
+        let price = match self.order_type {
+            OrderType::LimitOrder(price) => price,
+            OrderType::MarketOrder => return Ok(()),
+        };
+        let tick = metadata.tick_size();
+        if tick > 0.0 && ((price / tick).round() * tick - price).abs() > 1e-6 {
+            return Err(Error::OffTick(price, tick));
+        }
+        match metadata.price_band() {
+            Some((lower, upper)) if price < lower || price > upper => {
+                Err(Error::OutsidePriceBand(price, lower, upper))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // `validate` against the contract metadata from the configured provider.
+    pub fn validate_contract(&self) -> Result<(), Error> {
+        self.validate(&self.scrip.get_contract_metadata()?)
+    }
+}
+
+// =============================================================================
+//                            Algorithmic Execution
+// =============================================================================
+
+impl Order {
+    pub fn twap(&self, slices: u32, duration: Duration) -> AlgoExecution {
+        AlgoExecution::new(self.clone(), ExecutionAlgo::Twap { slices, duration }, Local::now())
+    }
+
+    pub fn vwap(&self, profile: Vec<f64>, duration: Duration) -> AlgoExecution {
+        AlgoExecution::new(self.clone(), ExecutionAlgo::Vwap { profile, duration }, Local::now())
+    }
+
+    pub fn iceberg(&self, visible_quantity: u32) -> AlgoExecution {
+        AlgoExecution::new(self.clone(), ExecutionAlgo::Iceberg { visible_quantity }, Local::now())
+    }
 }
 
 // =============================================================================
@@ -108,7 +208,17 @@ impl Order {
 
 #[derive(Clone, Debug)]
 pub enum BasketOrderType {
+    // Every leg is checked against the depth before execution. If a leg still
+    // fails, the legs already filled are squared off.
     AllOrNone,
+    // Executes every leg and skips the ones that fail.
+    BestEffort,
+    // Executes the legs in order and stops at the first failure, keeping the
+    // legs that were filled.
+    Sequential,
+    // Same as `AllOrNone`, but buy legs are executed before sell legs so that
+    // the hedges are in place before the margin heavy short legs.
+    HedgeFirst,
 }
 
 #[derive(Clone, Debug)]
@@ -136,20 +246,85 @@ impl BasketOrder {
         Ok(position)
     }
 
-    pub fn execute(&self) -> Result<Position, Error> {
-        let mut position: Position = Default::default();
-        self.orders.iter().try_for_each(|x| {
-            let transaction = match x.execute() {
-                Ok(t) => t,
-                Err(e) => return Err(e),
-            };
-            position.add_transaction(transaction);
-            Ok(())
+    // Checks every leg against `gate` before executing any of them.
+    pub fn execute(&self, gate: &RiskGate, position: &Position) -> Result<Position, Error> {
+        gate.check_basket(self, position)?;
+        self.execute_unchecked()
+    }
+
+    // Executes without any pre-trade checks, see `Order::execute_unchecked`.
+    pub fn execute_unchecked(&self) -> Result<Position, Error> {
+        match self.basket_order_type {
+            BasketOrderType::AllOrNone => self.execute_atomic(&(0..self.orders.len()).collect::<Vec<usize>>()),
+            BasketOrderType::HedgeFirst => self.execute_atomic(&self.hedge_first_legs()),
+            BasketOrderType::BestEffort => Ok(self.execute_best_effort()),
+            BasketOrderType::Sequential => Ok(self.execute_sequential()),
+        }
+    }
+
+    // Indices of the buy legs followed by the sell legs, each in their
+    // original order.
+    fn hedge_first_legs(&self) -> Vec<usize> {
+        let (buys, sells): (Vec<usize>, Vec<usize>) = (0..self.orders.len())
+            .partition(|i| self.orders[*i].quantity > 0);
+        buys.into_iter().chain(sells).collect()
+    }
+
+    // Pre-checks every leg against the current depth and unwinds the filled
+    // legs if one of them fails during execution. `legs` are indices into
+    // `orders` in the order of execution, and errors refer to them.
+    fn execute_atomic(&self, legs: &[usize]) -> Result<Position, Error> {
+        legs.iter().try_for_each(|i| {
+            self.orders[*i].fill_check().map_err(|e| Error::BasketLegFailed(*i, Box::new(e)))
         })?;
 
+        let mut filled: Vec<(usize, Transaction)> = Vec::new();
+        for i in legs.iter() {
+            match self.orders[*i].execute_unchecked() {
+                Ok(t) => filled.push((*i, t)),
+                Err(e) => {
+                    let failed = Error::BasketLegFailed(*i, Box::new(e));
+                    return Err(match Self::unwind(&filled) {
+                        Ok(()) => failed,
+                        Err((leg, e)) => Error::UnwindFailed(leg, Box::new(e), Box::new(failed)),
+                    });
+                }
+            }
+        }
+
+        let mut position: Position = Default::default();
+        filled.into_iter().for_each(|(_, t)| position.add_transaction(t));
         Ok(position)
     }
 
+    // Squares off the filled legs, latest first.
+    fn unwind(filled: &[(usize, Transaction)]) -> Result<(), (usize, Error)> {
+        filled.iter().rev().try_for_each(|(i, t)| {
+            Order::reverse(t)
+                .execute_unchecked()
+                .map(|_| ())
+                .map_err(|e| (*i, e))
+        })
+    }
+
+    fn execute_best_effort(&self) -> Position {
+        let mut position: Position = Default::default();
+        self.orders
+            .iter()
+            .filter_map(|x| x.execute_unchecked().ok())
+            .for_each(|t| position.add_transaction(t));
+        position
+    }
+
+    fn execute_sequential(&self) -> Position {
+        let mut position: Position = Default::default();
+        self.orders
+            .iter()
+            .map_while(|x| x.execute_unchecked().ok())
+            .for_each(|t| position.add_transaction(t));
+        position
+    }
+
     pub fn margin(&self) -> Result<f64, Error> {
         self.orders.iter().try_fold(0.0, |x, y| Ok(x + y.margin()?))
     }
@@ -192,6 +367,27 @@ mod tests {
         assert_eq!(sell_5.avg_price(), Ok(400.1425));
     }
 
+    #[test]
+    fn contract_validation() {
+        use crate::provider::InMemoryProvider;
+        use crate::error::Error;
+        let provider = InMemoryProvider::from_json(r#"[
+            {"scrip": "TEST", "type": "cash", "exchange": "nse", "lotSize": 5,
+             "tickSize": 0.05, "lowerCircuit": 360.2, "upperCircuit": 440.25}
+        ]"#).unwrap();
+        let scrip = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let metadata = scrip.get_contract_metadata_from(&provider).unwrap();
+
+        assert_eq!(Order::new(scrip.clone(), 10, OrderType::LimitOrder(400.15)).validate(&metadata), Ok(()));
+        assert_eq!(Order::new(scrip.clone(), -10, OrderType::MarketOrder).validate(&metadata), Ok(()));
+        assert_eq!(Order::new(scrip.clone(), 8, OrderType::MarketOrder).validate(&metadata), Err(Error::NotLotMultiple(8, 5)));
+        assert_eq!(Order::new(scrip.clone(), 5, OrderType::LimitOrder(400.12)).validate(&metadata), Err(Error::OffTick(400.12, 0.05)));
+        assert_eq!(
+            Order::new(scrip, 5, OrderType::LimitOrder(450.0)).validate(&metadata),
+            Err(Error::OutsidePriceBand(450.0, 360.2, 440.25))
+        );
+    }
+
     #[test]
     fn unlimited_marketorder_buy() {
         let scrip = StockScrip::new("TEST", "NSE", "C");
@@ -214,4 +410,60 @@ mod tests {
         assert_eq!(sell_all.avg_price(), Err(error::Error::InsufficientDepth(6)));
     }
 
+    #[test]
+    fn limitorder_fill_check() {
+        let scrip = scrip::Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let within_limit = Order::new(scrip.clone(), 9, OrderType::LimitOrder(402.13));
+        let beyond_limit = Order::new(scrip, 10, OrderType::LimitOrder(402.13));
+        assert_eq!(within_limit.fill_check(), Ok(()));
+        assert_eq!(beyond_limit.fill_check(), Err(error::Error::InsufficientDepth(1)));
+    }
+
+    fn test_basket(basket_order_type: BasketOrderType) -> BasketOrder {
+        let first = scrip::Scrip::Stock(StockScrip::new("FIRST", "NSE", "C"));
+        let second = scrip::Scrip::Stock(StockScrip::new("SECOND", "NSE", "C"));
+        let third = scrip::Scrip::Stock(StockScrip::new("THIRD", "NSE", "C"));
+        BasketOrder {
+            basket_order_type,
+            orders: vec![
+                Order::new(first, -8, OrderType::MarketOrder),
+                Order::new(second, 20, OrderType::MarketOrder),
+                Order::new(third, 8, OrderType::MarketOrder),
+            ],
+        }
+    }
+
+    #[test]
+    fn all_or_none_basket() {
+        let basket = test_basket(BasketOrderType::AllOrNone);
+        assert_eq!(
+            basket.execute(&RiskGate::new(), &Default::default()).err(),
+            Some(error::Error::BasketLegFailed(1, Box::new(error::Error::InsufficientDepth(6))))
+        );
+    }
+
+    #[test]
+    fn best_effort_basket() {
+        let position = test_basket(BasketOrderType::BestEffort).execute_unchecked().unwrap();
+        assert_eq!(position.history.len(), 2);
+    }
+
+    #[test]
+    fn sequential_basket() {
+        let position = test_basket(BasketOrderType::Sequential).execute_unchecked().unwrap();
+        assert_eq!(position.history.len(), 1);
+    }
+
+    #[test]
+    fn hedge_first_ordering() {
+        let basket = test_basket(BasketOrderType::HedgeFirst);
+        assert_eq!(basket.hedge_first_legs(), vec![1, 2, 0]);
+
+        // The failed leg is reported by its index in the basket.
+        assert_eq!(
+            basket.execute_unchecked().err(),
+            Some(error::Error::BasketLegFailed(1, Box::new(error::Error::InsufficientDepth(6))))
+        );
+    }
+
 }
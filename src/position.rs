@@ -1,8 +1,11 @@
 use crate::scrip::Scrip;
-use crate::redis_utils::RedisScrip;
+use crate::redis_utils::fetch_tickers;
+use crate::lots::{LotBook, LotPolicy};
 use chrono::prelude::*;
 use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transaction {
     pub scrip: Scrip,
     pub quantity: i32,
@@ -10,26 +13,54 @@ pub struct Transaction {
     pub exec_time: DateTime<Local>,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Position {
     pub history: Vec<Transaction>,
+    // Open quantity and its average cost. Flat holdings are removed.
+    #[serde(with = "scrip_map")]
     pub holding: HashMap<Scrip, (i32, f64)>,
+    // P&L booked on the quantity that has been closed, per scrip.
+    #[serde(with = "scrip_map")]
+    pub realized: HashMap<Scrip, f64>,
+    pub lots: LotBook,
 }
 
 impl Position {
-    pub fn get_pnl(&self) -> f64 {
-        self.holding.iter().fold(0.0, |x, (s, (q, p))| {
-            x + (*q as f64) * (s.updated_ticker().ltp - p)
-        })
+    pub fn with_lot_policy(policy: LotPolicy) -> Self {
+        Self {
+            lots: LotBook::new(policy),
+            ..Default::default()
+        }
+    }
+
+    // Total of realized and unrealized P&L.
+    pub fn get_pnl(&self) -> redis::RedisResult<f64> {
+        Ok(self.realized_pnl() + self.unrealized_pnl()?)
+    }
+
+    pub fn realized_pnl(&self) -> f64 {
+        self.realized.values().sum()
+    }
+
+    pub fn unrealized_pnl(&self) -> redis::RedisResult<f64> {
+        let scrips: Vec<Scrip> = self.holding.keys().cloned().collect();
+        let tickers = fetch_tickers(&scrips)?;
+        Ok(self.holding.iter().fold(0.0, |x, (s, (q, p))| match tickers.get(s) {
+            Some(ticker) => x + (*q as f64) * (ticker.ltp - p),
+            None => x,
+        }))
     }
 
     pub fn add_transaction(&mut self, transaction: Transaction) {
         self.update_holding(transaction.scrip.clone(), transaction.quantity, transaction.avg_price);
+        self.lots.add_transaction(&transaction);
         self.history.push(transaction);
     }
 
     pub fn extend(&mut self, new_position: Position) {
+        new_position.history.iter().for_each(|t| self.lots.add_transaction(t));
         self.history.extend(new_position.history);
+        new_position.realized.into_iter().for_each(|(s, r)| self.book_realized(s, r));
         new_position.holding.into_iter().for_each(|(s, (q, p))| self.update_holding(s, q, p));
     }
 
@@ -37,21 +68,129 @@ impl Position {
         self.history.sort_by(|x, y| x.exec_time.partial_cmp(&y.exec_time).unwrap());
     }
 
-    pub fn update_holding(&mut self, scrip: Scrip, quantity: i32, price: f64) {
-        match self.holding.get_mut(&scrip) {
-            Some(h) => {
-                let total_cost =
-                    (h.1 * (h.0 as f64)) + (quantity as f64 * price);
-                let avg_price = total_cost / ((h.0 + quantity) as f64);
-                h.0 += quantity;
-                h.1 = avg_price;
-            }
-            None => {
-                self.holding.insert(
-                    scrip,
-                    (quantity, price),
-                );
-            }
-        };
+    // Adding to a holding averages the cost. Reducing it books the P&L of the
+    // closed quantity and leaves the cost untouched. A trade that flips the
+    // holding closes it completely and opens the remainder at `price`.
+    // Leaves the lots untouched, so outside the crate holdings only change
+    // through `add_transaction`.
+    pub(crate) fn update_holding(&mut self, scrip: Scrip, quantity: i32, price: f64) {
+        if quantity == 0 {
+            return;
+        }
+
+        let (held, avg_price) = self.holding.get(&scrip).copied().unwrap_or((0, 0.0));
+        if held == 0 || held.signum() == quantity.signum() {
+            let total_cost = (avg_price * (held as f64)) + (quantity as f64 * price);
+            let total_quantity = held + quantity;
+            self.holding.insert(scrip, (total_quantity, total_cost / (total_quantity as f64)));
+            return;
+        }
+
+        let closed = held.abs().min(quantity.abs()) * held.signum();
+        self.book_realized(scrip.clone(), (closed as f64) * (price - avg_price));
+
+        let remaining = held + quantity;
+        if remaining == 0 {
+            self.holding.remove(&scrip);
+        } else if remaining.signum() == held.signum() {
+            self.holding.insert(scrip, (remaining, avg_price));
+        } else {
+            self.holding.insert(scrip, (remaining, price));
+        }
+    }
+
+    fn book_realized(&mut self, scrip: Scrip, pnl: f64) {
+        *self.realized.entry(scrip).or_insert(0.0) += pnl;
+    }
+}
+
+// Scrips are not plain strings, so maps keyed by them are serialized as a list
+// of (scrip, value) pairs.
+pub(crate) mod scrip_map {
+    use crate::scrip::Scrip;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<V, S>(map: &HashMap<Scrip, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, V, D>(deserializer: D) -> Result<HashMap<Scrip, V>, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs: Vec<(Scrip, V)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn test_scrip() -> Scrip {
+        Scrip::Stock(StockScrip::new("TEST", "NSE", "C"))
+    }
+
+    #[test]
+    fn flat_holding() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 10, 100.0);
+        position.update_holding(test_scrip(), -10, 110.0);
+        assert!(position.holding.is_empty());
+        assert_eq!(position.realized_pnl(), 100.0);
+    }
+
+    #[test]
+    fn partial_close() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), -10, 100.0);
+        position.update_holding(test_scrip(), 4, 90.0);
+        assert_eq!(position.holding.get(&test_scrip()), Some(&(-6, 100.0)));
+        assert_eq!(position.realized_pnl(), 40.0);
+    }
+
+    #[test]
+    fn flipped_holding() {
+        let mut position: Position = Default::default();
+        position.update_holding(test_scrip(), 10, 100.0);
+        position.update_holding(test_scrip(), 10, 110.0);
+        position.update_holding(test_scrip(), -25, 120.0);
+        assert_eq!(position.holding.get(&test_scrip()), Some(&(-5, 120.0)));
+        assert_eq!(position.realized_pnl(), 300.0);
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut position: Position = Default::default();
+        position.add_transaction(Transaction {
+            scrip: test_scrip(),
+            quantity: 10,
+            avg_price: 100.0,
+            exec_time: chrono::Local::now(),
+        });
+        position.add_transaction(Transaction {
+            scrip: test_scrip(),
+            quantity: -4,
+            avg_price: 110.0,
+            exec_time: chrono::Local::now(),
+        });
+
+        let json = serde_json::to_string(&position).unwrap();
+        let restored: Position = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.holding.get(&test_scrip()), Some(&(6, 100.0)));
+        assert_eq!(restored.realized_pnl(), 40.0);
+        assert_eq!(restored.lots.open_lots(&test_scrip())[0].quantity, 6);
+        assert_eq!(restored.lots.realized(), restored.realized_pnl());
+        assert_eq!(restored.history.len(), 2);
     }
 }
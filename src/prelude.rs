@@ -14,3 +14,31 @@ pub use crate::position::{Transaction, Position};
 pub use crate::orders::{Order, OrderType, BasketOrder, BasketOrderType};
 #[doc(no_inline)]
 pub use crate::live_candle::Candle;
+#[doc(no_inline)]
+pub use crate::risk::RiskGate;
+#[doc(no_inline)]
+pub use crate::algo::{ExecutionAlgo, AlgoExecution};
+#[doc(no_inline)]
+pub use crate::lots::{LotPolicy, LotBook};
+#[doc(no_inline)]
+pub use crate::account::Account;
+#[doc(no_inline)]
+pub use crate::pricing::{BlackScholes, Greeks};
+#[doc(no_inline)]
+pub use crate::exposure::{Exposure, PortfolioExposure};
+#[doc(no_inline)]
+pub use crate::monitor::{ExitRule, RiskMonitor};
+#[doc(no_inline)]
+pub use crate::session::{MarketSession, SessionPhase};
+#[doc(no_inline)]
+pub use crate::calendar::TradingCalendar;
+#[doc(no_inline)]
+pub use crate::expiry::{ExpiryCalendar, ExpiryRules};
+#[doc(no_inline)]
+pub use crate::instruments::{Instrument, InstrumentMaster};
+#[doc(no_inline)]
+pub use crate::index::IndexReconstruction;
+#[doc(no_inline)]
+pub use crate::subscription::{Subscription, UpdateSource};
+#[doc(no_inline)]
+pub use crate::writer::TickerWriter;
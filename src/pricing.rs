@@ -0,0 +1,257 @@
+use crate::options::{OptionScrip, OptionType};
+use crate::redis_utils::RedisScrip;
+use crate::session::MarketSession;
+use chrono::prelude::*;
+use std::f64::consts::{PI, SQRT_2};
+
+// =============================================================================
+//                             Black Scholes Pricer
+// =============================================================================
+
+const DAYS_IN_YEAR: f64 = 365.0;
+
+// Volatility reported for prices at or below the model's floor.
+const MIN_VOLATILITY: f64 = 1e-4;
+
+// Vega is per volatility point (0.01) and theta is per calendar day.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,
+    pub gamma: f64,
+    pub vega: f64,
+    pub theta: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlackScholes {
+    pub spot: f64,
+    pub strike: f64,
+    pub rate: f64,
+    pub volatility: f64,
+    // Time to expiry in years.
+    pub time: f64,
+    pub option_type: OptionType,
+}
+
+impl BlackScholes {
+    fn d1_d2(&self) -> (f64, f64) {
+        let vol_time = self.volatility * self.time.sqrt();
+        let d1 = ((self.spot / self.strike).ln()
+                  + (self.rate + self.volatility.powi(2) / 2.0) * self.time) / vol_time;
+        (d1, d1 - vol_time)
+    }
+
+    fn intrinsic(&self) -> f64 {
+        match self.option_type {
+            OptionType::CE => (self.spot - self.strike).max(0.0),
+            OptionType::PE => (self.strike - self.spot).max(0.0),
+        }
+    }
+
+    fn is_degenerate(&self) -> bool {
+        self.time <= 0.0 || self.volatility <= 0.0
+    }
+
+    pub fn price(&self) -> f64 {
+        if self.is_degenerate() {
+            return self.intrinsic();
+        }
+
+        let (d1, d2) = self.d1_d2();
+        let discount = (-self.rate * self.time).exp();
+        match self.option_type {
+            OptionType::CE => self.spot * norm_cdf(d1) - self.strike * discount * norm_cdf(d2),
+            OptionType::PE => self.strike * discount * norm_cdf(-d2) - self.spot * norm_cdf(-d1),
+        }
+    }
+
+    pub fn greeks(&self) -> Greeks {
+        if self.is_degenerate() {
+            let delta = match self.option_type {
+                OptionType::CE if self.spot > self.strike => 1.0,
+                OptionType::PE if self.spot < self.strike => -1.0,
+                _ => 0.0,
+            };
+            return Greeks { delta, ..Default::default() };
+        }
+
+        let (d1, d2) = self.d1_d2();
+        let discount = (-self.rate * self.time).exp();
+        let sqrt_time = self.time.sqrt();
+        let gamma = norm_pdf(d1) / (self.spot * self.volatility * sqrt_time);
+        let vega = self.spot * norm_pdf(d1) * sqrt_time / 100.0;
+        let decay = -self.spot * norm_pdf(d1) * self.volatility / (2.0 * sqrt_time);
+        let (delta, theta) = match self.option_type {
+            OptionType::CE => (
+                norm_cdf(d1),
+                decay - self.rate * self.strike * discount * norm_cdf(d2),
+            ),
+            OptionType::PE => (
+                norm_cdf(d1) - 1.0,
+                decay + self.rate * self.strike * discount * norm_cdf(-d2),
+            ),
+        };
+
+        Greeks { delta, gamma, vega, theta: theta / DAYS_IN_YEAR }
+    }
+
+    // Volatility at which the model price matches `price`, by bisection.
+    // Prices at or below intrinsic are clamped to it and get the floor
+    // volatility. Returns `None` at expiry or if the price is above what the
+    // model can produce.
+    pub fn implied_volatility(&self, price: f64) -> Option<f64> {
+        if self.time <= 0.0 {
+            return None;
+        }
+
+        let price = price.max(self.intrinsic());
+        let mut model = self.clone();
+        let (mut low, mut high) = (MIN_VOLATILITY, 5.0);
+        model.volatility = low;
+        if model.price() >= price {
+            return Some(MIN_VOLATILITY);
+        }
+        model.volatility = high;
+        if model.price() < price {
+            return None;
+        }
+
+        for _ in 0..100 {
+            let mid = (low + high) / 2.0;
+            model.volatility = mid;
+            if model.price() > price {
+                high = mid;
+            } else {
+                low = mid;
+            }
+            if high - low < 1e-6 {
+                break;
+            }
+        }
+        Some((low + high) / 2.0)
+    }
+}
+
+// Close of trading on the expiry day, or the end of the day if the session
+// has no trading on it.
+pub fn expiry_close(expiry: NaiveDate, session: &MarketSession) -> DateTime<Utc> {
+    session.close_on(expiry).unwrap_or_else(|| {
+        let end = expiry.and_hms_opt(23, 59, 59).unwrap();
+        session.timezone.from_local_datetime(&end).unwrap().with_timezone(&Utc)
+    })
+}
+
+// Year fraction till the expiry close.
+pub fn time_to_expiry(expiry: NaiveDate, now: DateTime<Local>, session: &MarketSession) -> f64 {
+    let expiry = expiry_close(expiry, session);
+    let seconds = (expiry - now.with_timezone(&Utc)).num_seconds().max(0);
+    seconds as f64 / (DAYS_IN_YEAR * 24.0 * 60.0 * 60.0)
+}
+
+pub fn norm_pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * PI).sqrt()
+}
+
+// Abramowitz & Stegun 7.1.26 approximation of erf.
+pub fn norm_cdf(x: f64) -> f64 {
+    let z = x.abs() / SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741
+               + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    match x >= 0.0 {
+        true => 0.5 * (1.0 + erf),
+        false => 0.5 * (1.0 - erf),
+    }
+}
+
+impl OptionScrip {
+    // Model priced at the implied volatility of the current `ltp`, with the
+    // underlying's `ltp` as spot. `None` without an underlying or a valid
+    // implied volatility.
+    pub fn pricer(&self, rate: f64, now: DateTime<Local>) -> Option<BlackScholes> {
+        let spot = self.underlying.as_ref()?.updated_ticker().ltp;
+        self.pricer_at(spot, self.updated_ticker().ltp, rate, now)
+    }
+
+    // `pricer` with the underlying's and the option's `ltp` already fetched.
+    pub fn pricer_at(&self, spot: f64, ltp: f64, rate: f64, now: DateTime<Local>) -> Option<BlackScholes> {
+        let mut model = BlackScholes {
+            spot,
+            strike: self.strike as f64,
+            rate,
+            volatility: 0.0,
+            time: time_to_expiry(self.expiry, now, &MarketSession::for_exchange(self.exchange)),
+            option_type: self.option_type.clone(),
+        };
+        if model.time > 0.0 {
+            model.volatility = model.implied_volatility(ltp)?;
+        }
+        Some(model)
+    }
+
+    pub fn greeks(&self, rate: f64, now: DateTime<Local>) -> Option<Greeks> {
+        self.pricer(rate, now).map(|x| x.greeks())
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::pricing::*;
+
+    fn atm_call() -> BlackScholes {
+        BlackScholes {
+            spot: 100.0,
+            strike: 100.0,
+            rate: 0.05,
+            volatility: 0.2,
+            time: 1.0,
+            option_type: OptionType::CE,
+        }
+    }
+
+    #[test]
+    fn call_price_and_greeks() {
+        let call = atm_call();
+        assert!((call.price() - 10.4506).abs() < 1e-3);
+        let greeks = call.greeks();
+        assert!((greeks.delta - 0.6368).abs() < 1e-3);
+        assert!((greeks.gamma - 0.01876).abs() < 1e-4);
+        assert!((greeks.vega - 0.3752).abs() < 1e-3);
+    }
+
+    #[test]
+    fn put_call_parity() {
+        let call = atm_call();
+        let put = BlackScholes { option_type: OptionType::PE, ..atm_call() };
+        let forward = call.spot - call.strike * (-call.rate * call.time).exp();
+        assert!((call.price() - put.price() - forward).abs() < 1e-6);
+    }
+
+    #[test]
+    fn implied_volatility() {
+        let call = atm_call();
+        let iv = call.implied_volatility(call.price()).unwrap();
+        assert!((iv - 0.2).abs() < 1e-4);
+
+        let deep = BlackScholes { strike: 50.0, ..atm_call() };
+        assert_eq!(deep.implied_volatility(40.0), Some(MIN_VOLATILITY));
+        assert_eq!(deep.implied_volatility(500.0), None);
+    }
+
+    #[test]
+    fn expiry_at_session_close() {
+        let nse = MarketSession::for_exchange(crate::scrip::Exchange::NSE);
+        let expiry = NaiveDate::from_ymd_opt(2022, 6, 30).unwrap();
+        let now = FixedOffset::east_opt(19800).unwrap()
+            .with_ymd_and_hms(2022, 6, 30, 14, 30, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        let hour = 1.0 / (DAYS_IN_YEAR * 24.0);
+        assert!((time_to_expiry(expiry, now, &nse) - hour).abs() < 1e-9);
+    }
+}
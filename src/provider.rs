@@ -0,0 +1,568 @@
+use aws_sdk_dynamodb::model::AttributeValue;
+use crate::info::{MetaData, TABLE_NAME};
+use crate::scrip::Exchange;
+use crate::calendar::TradingCalendar;
+use crate::metadata_client::BlockingMetadataClient;
+use crate::error::MetadataError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use cached::{Cached, TimedSizedCache};
+
+pub static DEFAULT_CACHE_SIZE: usize = 10_000;
+pub static DEFAULT_CACHE_TTL_SEC: u64 = 60 * 60;
+
+// =============================================================================
+//                              Metadata Providers
+// =============================================================================
+
+// Source of scrip metadata, looked up by scrip name.
+pub trait MetadataProvider: Send + Sync {
+    fn get(&self, name: &str) -> Result<MetaData, MetadataError>;
+
+    // Names that are not found or whose metadata is malformed are left out of
+    // the result; `get` reports why.
+    fn batch_get(&self, names: &[String]) -> Result<HashMap<String, MetaData>, MetadataError> {
+        let mut metadata: HashMap<String, MetaData> = HashMap::new();
+        for name in names.iter() {
+            match self.get(name) {
+                Ok(m) => { metadata.insert(name.to_string(), m); },
+                Err(MetadataError::NotFound(_)) | Err(MetadataError::Malformed(..)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(metadata)
+    }
+
+    // Stores metadata under `name`. Providers are read-only unless they
+    // override this.
+    fn put(&self, name: &str, metadata: MetaData) -> Result<(), MetadataError> {
+        Err(MetadataError::Transport(String::from("provider is read-only")))
+    }
+
+    // Holidays and special sessions of `exchange`. Providers without calendar
+    // data report it as not found.
+    fn calendar(&self, exchange: Exchange) -> Result<TradingCalendar, MetadataError> {
+        Err(MetadataError::NotFound(format!("{:?} calendar", exchange)))
+    }
+
+    // Drops any cached metadata for `name`. No-op for uncached providers.
+    fn invalidate(&self, name: &str) {}
+
+    // Fetches everything cached again from the source.
+    fn refresh_all(&self) -> Result<(), MetadataError> {
+        Ok(())
+    }
+}
+
+// =============================================================================
+//                                  DynamoDB
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct DynamoConfig {
+    pub table: String,
+    // e.g. "http://localhost:8000" for DynamoDB Local.
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+}
+
+impl Default for DynamoConfig {
+    fn default() -> Self {
+        Self {
+            table: TABLE_NAME.to_string(),
+            endpoint: None,
+            region: None,
+        }
+    }
+}
+
+pub struct DynamoProvider {
+    pub client: BlockingMetadataClient,
+}
+
+impl DynamoProvider {
+    pub fn new(config: &DynamoConfig) -> Result<Self, MetadataError> {
+        let client = BlockingMetadataClient::from_config(
+            &config.table,
+            config.endpoint.as_deref(),
+            config.region.as_deref(),
+        )?;
+        Ok(Self { client })
+    }
+}
+
+impl MetadataProvider for DynamoProvider {
+    fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        self.client.get(name)
+    }
+
+    fn batch_get(&self, names: &[String]) -> Result<HashMap<String, MetaData>, MetadataError> {
+        self.client.batch_get(names).map(|x| x.metadata)
+    }
+
+    fn put(&self, name: &str, metadata: MetaData) -> Result<(), MetadataError> {
+        self.client.put(name, &metadata)
+    }
+}
+
+// =============================================================================
+//                                  In Memory
+// =============================================================================
+
+#[derive(Default)]
+pub struct InMemoryProvider {
+    pub metadata: RwLock<HashMap<String, MetaData>>,
+    pub calendars: RwLock<HashMap<Exchange, TradingCalendar>>,
+}
+
+impl InMemoryProvider {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&self, name: &str, metadata: MetaData) {
+        self.metadata.write().unwrap().insert(name.to_string(), metadata);
+    }
+
+    pub fn insert_calendar(&self, calendar: TradingCalendar) {
+        self.calendars.write().unwrap().insert(calendar.exchange, calendar);
+    }
+
+    // Items in the same layout as the DynamoDB table, keyed by their `scrip`
+    // attribute.
+    pub fn from_items(items: &[HashMap<String, AttributeValue>]) -> Result<Self, MetadataError> {
+        let provider = Self::new();
+        for item in items.iter() {
+            let metadata = MetaData::from_response(item)?;
+            match item.get("scrip") {
+                Some(AttributeValue::S(name)) => provider.insert(name, metadata),
+                _ => return Err(MetadataError::Malformed(String::from("scrip"), String::from("missing"))),
+            }
+        }
+        Ok(provider)
+    }
+
+    // A JSON array of items, with attributes named as in the DynamoDB table.
+    pub fn from_json(json: &str) -> Result<Self, MetadataError> {
+        Self::from_items(&json_items(json)?)
+    }
+
+    // A header row with attribute names followed by one item per row. Values
+    // with commas must be quoted. Constituents are written as `SBIN=2.5;INFY=6.1`.
+    pub fn from_csv(csv: &str) -> Result<Self, MetadataError> {
+        Self::from_items(&csv_items(csv))
+    }
+}
+
+impl MetadataProvider for InMemoryProvider {
+    fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        self.metadata
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| MetadataError::NotFound(name.to_string()))
+    }
+
+    fn put(&self, name: &str, metadata: MetaData) -> Result<(), MetadataError> {
+        self.insert(name, metadata);
+        Ok(())
+    }
+
+    fn calendar(&self, exchange: Exchange) -> Result<TradingCalendar, MetadataError> {
+        self.calendars
+            .read()
+            .unwrap()
+            .get(&exchange)
+            .cloned()
+            .ok_or_else(|| MetadataError::NotFound(format!("{:?} calendar", exchange)))
+    }
+}
+
+// Parses a JSON array of objects into table items.
+pub(crate) fn json_items(json: &str) -> Result<Vec<HashMap<String, AttributeValue>>, MetadataError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| MetadataError::Malformed(String::from("json"), e.to_string()))?;
+    match json_to_attribute(&value) {
+        AttributeValue::L(items) => items
+            .into_iter()
+            .map(|x| match x {
+                AttributeValue::M(item) => Ok(item),
+                _ => Err(MetadataError::Malformed(String::from("json"), String::from("expected objects"))),
+            })
+            .collect(),
+        _ => Err(MetadataError::Malformed(String::from("json"), String::from("expected an array"))),
+    }
+}
+
+// Parses CSV with a header row into table items. Empty cells are left out.
+pub(crate) fn csv_items(csv: &str) -> Vec<HashMap<String, AttributeValue>> {
+    let mut lines = csv.lines().filter(|x| !x.trim().is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some(h) => split_csv_line(h),
+        None => return Vec::new(),
+    };
+
+    lines
+        .map(|line| {
+            header
+                .iter()
+                .zip(split_csv_line(line))
+                .filter(|(_, v)| !v.is_empty())
+                .map(|(k, v)| (k.to_string(), csv_to_attribute(k, &v)))
+                .collect()
+        })
+        .collect()
+}
+
+// Splits a CSV line into trimmed fields. Fields in double quotes can contain
+// commas, and two double quotes inside them are a literal one.
+pub(crate) fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+// Attributes stored as numbers in the table.
+const NUMERIC_ATTRIBUTES: [&str; 7] = [
+    "freeFloatMarketCap", "lotSize", "tickSize", "lowerCircuit", "upperCircuit",
+    "freezeQuantity", "strike",
+];
+
+fn csv_to_attribute(key: &str, value: &str) -> AttributeValue {
+    if key == "constituents" {
+        let weights = value
+            .split(';')
+            .filter_map(|x| x.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), AttributeValue::N(v.trim().to_string())))
+            .collect();
+        return AttributeValue::M(weights);
+    }
+    match NUMERIC_ATTRIBUTES.contains(&key) {
+        true => AttributeValue::N(value.to_string()),
+        false => AttributeValue::S(value.to_string()),
+    }
+}
+
+fn json_to_attribute(value: &serde_json::Value) -> AttributeValue {
+    match value {
+        serde_json::Value::Null => AttributeValue::Null(true),
+        serde_json::Value::Bool(b) => AttributeValue::Bool(*b),
+        serde_json::Value::Number(n) => AttributeValue::N(n.to_string()),
+        serde_json::Value::String(s) => AttributeValue::S(s.to_string()),
+        serde_json::Value::Array(a) => AttributeValue::L(a.iter().map(json_to_attribute).collect()),
+        serde_json::Value::Object(o) => AttributeValue::M(
+            o.iter().map(|(k, v)| (k.to_string(), json_to_attribute(v))).collect()
+        ),
+    }
+}
+
+// =============================================================================
+//                                    File
+// =============================================================================
+
+#[derive(Copy, Clone, Debug)]
+pub enum FileFormat {
+    Json,
+    Csv,
+}
+
+// Loads the whole file into memory. `reload` picks up changes to the file.
+pub struct FileProvider {
+    pub path: PathBuf,
+    pub format: FileFormat,
+    data: RwLock<InMemoryProvider>,
+}
+
+impl FileProvider {
+    pub fn new(path: &Path, format: FileFormat) -> Result<Self, MetadataError> {
+        let data = RwLock::new(Self::load(path, format)?);
+        Ok(Self { path: path.to_path_buf(), format, data })
+    }
+
+    fn load(path: &Path, format: FileFormat) -> Result<InMemoryProvider, MetadataError> {
+        let contents = read_file(path)?;
+        match format {
+            FileFormat::Json => InMemoryProvider::from_json(&contents),
+            FileFormat::Csv => InMemoryProvider::from_csv(&contents),
+        }
+    }
+
+    pub fn reload(&self) -> Result<(), MetadataError> {
+        *self.data.write().unwrap() = Self::load(&self.path, self.format)?;
+        Ok(())
+    }
+}
+
+impl MetadataProvider for FileProvider {
+    fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        self.data.read().unwrap().get(name)
+    }
+
+    // Kept in memory only, and lost on `reload`.
+    fn put(&self, name: &str, metadata: MetaData) -> Result<(), MetadataError> {
+        self.data.read().unwrap().put(name, metadata)
+    }
+
+    fn calendar(&self, exchange: Exchange) -> Result<TradingCalendar, MetadataError> {
+        self.data.read().unwrap().calendar(exchange)
+    }
+
+    fn refresh_all(&self) -> Result<(), MetadataError> {
+        self.reload()
+    }
+}
+
+// Contents that are not valid UTF-8 are malformed, any other I/O failure is a
+// transport error.
+pub(crate) fn read_file(path: &Path) -> Result<String, MetadataError> {
+    std::fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData => MetadataError::Malformed(path.display().to_string(), e.to_string()),
+        _ => MetadataError::Transport(e.to_string()),
+    })
+}
+
+// =============================================================================
+//                                   Cached
+// =============================================================================
+
+// Bounded cache with expiry in front of another provider. Only successful
+// lookups are cached, so failures are retried on the next call.
+pub struct CachedProvider {
+    pub inner: Arc<dyn MetadataProvider>,
+    cache: Mutex<TimedSizedCache<String, MetaData>>,
+}
+
+impl CachedProvider {
+    // Holds at least one entry, as the cache cannot be created empty.
+    pub fn new(inner: Arc<dyn MetadataProvider>, size: usize, ttl_sec: u64) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(TimedSizedCache::with_size_and_lifespan(size.max(1), ttl_sec)),
+        }
+    }
+
+    fn store(&self, name: &str, metadata: MetaData) {
+        self.cache.lock().unwrap().cache_set(name.to_string(), metadata);
+    }
+}
+
+impl MetadataProvider for CachedProvider {
+    fn get(&self, name: &str) -> Result<MetaData, MetadataError> {
+        if let Some(metadata) = self.cache.lock().unwrap().cache_get(&name.to_string()) {
+            return Ok(metadata.clone());
+        }
+
+        let metadata = self.inner.get(name)?;
+        self.store(name, metadata.clone());
+        Ok(metadata)
+    }
+
+    fn batch_get(&self, names: &[String]) -> Result<HashMap<String, MetaData>, MetadataError> {
+        let mut metadata: HashMap<String, MetaData> = HashMap::new();
+        let mut missing: Vec<String> = Vec::new();
+        {
+            let mut cache = self.cache.lock().unwrap();
+            names.iter().for_each(|x| match cache.cache_get(x) {
+                Some(m) => { metadata.insert(x.to_string(), m.clone()); },
+                None => missing.push(x.to_string()),
+            });
+        }
+
+        if !missing.is_empty() {
+            let fetched = self.inner.batch_get(&missing)?;
+            fetched.iter().for_each(|(k, v)| self.store(k, v.clone()));
+            metadata.extend(fetched);
+        }
+        Ok(metadata)
+    }
+
+    fn put(&self, name: &str, metadata: MetaData) -> Result<(), MetadataError> {
+        self.inner.put(name, metadata)?;
+        self.invalidate(name);
+        Ok(())
+    }
+
+    fn calendar(&self, exchange: Exchange) -> Result<TradingCalendar, MetadataError> {
+        self.inner.calendar(exchange)
+    }
+
+    fn invalidate(&self, name: &str) {
+        self.cache.lock().unwrap().cache_remove(&name.to_string());
+        self.inner.invalidate(name);
+    }
+
+    // Refreshes the source, then fetches every name that is still cached.
+    // Names that are no longer found are dropped.
+    fn refresh_all(&self) -> Result<(), MetadataError> {
+        self.inner.refresh_all()?;
+        let names: Vec<String> = self.cache.lock().unwrap().key_order().cloned().collect();
+        let fetched = self.inner.batch_get(&names)?;
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.cache_clear();
+        fetched.into_iter().for_each(|(k, v)| { cache.cache_set(k, v); });
+        Ok(())
+    }
+}
+
+// =============================================================================
+//                              Injected Provider
+// =============================================================================
+
+lazy_static::lazy_static! {
+    static ref PROVIDER: RwLock<Option<Arc<dyn MetadataProvider>>> = RwLock::new(None);
+    // Created on first use. Left empty if that fails so the next call retries.
+    static ref DEFAULT_PROVIDER: Mutex<Option<Arc<dyn MetadataProvider>>> = Mutex::new(None);
+}
+
+fn default_provider() -> Result<Arc<dyn MetadataProvider>, MetadataError> {
+    let mut default = DEFAULT_PROVIDER.lock().unwrap();
+    if let Some(provider) = default.as_ref() {
+        return Ok(provider.clone());
+    }
+
+    let dynamo = DynamoProvider::new(&Default::default())?;
+    let cached = CachedProvider::new(Arc::new(dynamo), DEFAULT_CACHE_SIZE, DEFAULT_CACHE_TTL_SEC);
+    let provider: Arc<dyn MetadataProvider> = Arc::new(cached);
+    *default = Some(provider.clone());
+    Ok(provider)
+}
+
+// Provider used by `Scrip::get_metadata`. Without one, metadata is fetched
+// from the `scrip_info` table on DynamoDB through a `CachedProvider`. An
+// injected provider is used as is; wrap it in a `CachedProvider` if needed.
+pub fn set_metadata_provider(provider: Arc<dyn MetadataProvider>) {
+    *PROVIDER.write().unwrap() = Some(provider);
+}
+
+pub fn metadata_provider() -> Result<Arc<dyn MetadataProvider>, MetadataError> {
+    match PROVIDER.read().unwrap().clone() {
+        Some(provider) => Ok(provider),
+        None => default_provider(),
+    }
+}
+
+pub fn refresh_all() -> Result<(), MetadataError> {
+    metadata_provider()?.refresh_all()
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::provider::*;
+
+    #[test]
+    fn csv_provider() {
+        let csv = "scrip,type,exchange,timezone,freeFloatMarketCap,constituents\n\
+                   SBIN,cash,nse,+05:30,250000.5,\n\
+                   NIFTY,index,nse,+05:30,,SBIN=2.5;INFY=6.1\n";
+        let provider = InMemoryProvider::from_csv(csv).unwrap();
+        match provider.get("SBIN").unwrap() {
+            MetaData::Stock(s) => assert_eq!(s.free_float_market_cap, 250000.5),
+            m => panic!("Expected stock metadata, found {:?}", m),
+        }
+        match provider.get("NIFTY").unwrap() {
+            MetaData::Index(i) => assert_eq!(i.constituents.get("INFY"), Some(&6.1)),
+            m => panic!("Expected index metadata, found {:?}", m),
+        }
+        assert_eq!(provider.get("INFY").err(), Some(MetadataError::NotFound(String::from("INFY"))));
+    }
+
+    #[test]
+    fn quoted_csv_fields() {
+        let line = "1, \"STATE BANK, OF INDIA\" ,\"say \"\"hi\"\"\",,";
+        assert_eq!(split_csv_line(line), vec!["1", "STATE BANK, OF INDIA", "say \"hi\"", "", ""]);
+    }
+
+    #[test]
+    fn file_provider_errors() {
+        let dir = std::env::temp_dir().join(format!("ticker_rs_provider_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let json = dir.join("metadata.json");
+        std::fs::write(&json, r#"[{"scrip": "SBIN", "type": "cash", "exchange": "nse"}"#).unwrap();
+        assert!(matches!(FileProvider::new(&json, FileFormat::Json), Err(MetadataError::Malformed(..))));
+
+        let binary = dir.join("metadata.csv");
+        std::fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+        assert!(matches!(FileProvider::new(&binary, FileFormat::Csv), Err(MetadataError::Malformed(..))));
+
+        let missing = dir.join("missing.csv");
+        assert!(matches!(FileProvider::new(&missing, FileFormat::Csv), Err(MetadataError::Transport(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn injected_provider() {
+        use crate::{Scrip, StockScrip};
+        // The global provider is shared by every test, so this goes through a
+        // provider of its own.
+        let source = Arc::new(InMemoryProvider::from_csv("scrip,type,exchange\nSBIN,cash,nse\n").unwrap());
+        let provider = CachedProvider::new(source.clone(), 10, 60);
+
+        let sbin = Scrip::Stock(StockScrip::new("SBIN", "NSE", "C"));
+        assert!(matches!(sbin.get_metadata_from(&provider), Ok(MetaData::Stock(_))));
+        source.metadata.write().unwrap().clear();
+        assert!(sbin.get_metadata_from(&provider).is_ok());
+        provider.invalidate(&sbin.name());
+        assert!(sbin.get_metadata_from(&provider).is_err());
+    }
+
+    #[test]
+    fn cache_invalidation() {
+        let source = Arc::new(InMemoryProvider::from_csv("scrip,type,exchange\nSBIN,cash,nse\n").unwrap());
+        let cached = CachedProvider::new(source.clone(), 10, 60);
+        assert!(cached.get("INFY").is_err());
+
+        source.insert("INFY", cached.get("SBIN").unwrap());
+        assert!(cached.get("INFY").is_ok());
+
+        source.metadata.write().unwrap().clear();
+        assert!(cached.get("SBIN").is_ok());
+        cached.invalidate("SBIN");
+        assert_eq!(cached.get("SBIN").err(), Some(MetadataError::NotFound(String::from("SBIN"))));
+
+        cached.refresh_all().unwrap();
+        assert!(cached.get("INFY").is_err());
+    }
+
+    #[test]
+    fn zero_sized_cache() {
+        let source = Arc::new(InMemoryProvider::from_csv("scrip,type,exchange\nSBIN,cash,nse\n").unwrap());
+        let cached = CachedProvider::new(source, 0, 60);
+        assert!(cached.get("SBIN").is_ok());
+    }
+
+    #[test]
+    fn refresh_only_cached_names() {
+        let source = Arc::new(InMemoryProvider::from_csv("scrip,type,exchange\nSBIN,cash,nse\nINFY,cash,nse\n").unwrap());
+        let cached = CachedProvider::new(source.clone(), 1, 60);
+        cached.get("SBIN").unwrap();
+        cached.get("INFY").unwrap();
+
+        // SBIN was evicted when INFY was cached, so only INFY is fetched again.
+        cached.refresh_all().unwrap();
+        let names: Vec<String> = cached.cache.lock().unwrap().key_order().cloned().collect();
+        assert_eq!(names, vec![String::from("INFY")]);
+    }
+}
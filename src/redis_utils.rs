@@ -1,4 +1,6 @@
 use crate::tickers::Ticker;
+use crate::scrip::Scrip;
+use std::collections::HashMap;
 use chrono::{Utc, DateTime, NaiveDateTime, TimeZone};
 use crate::utils::POOL;
 use crate::live_candle::{Candle, DATETIME_FMT, EXPIRE_SEC};
@@ -14,6 +16,16 @@ pub trait RedisScrip {
         cmd
     }
 
+    // Channel the feed publishes to whenever the ticker hash is updated.
+    fn updates_channel(&self) -> String {
+        format!("{}:UPDATES", self.key())
+    }
+
+    // Stream of ticks appended by the feed.
+    fn ticks_key(&self) -> String {
+        format!("{}:TICKS", self.key())
+    }
+
     fn updated_ticker(&self) -> Ticker {
         let mut ticker = Ticker::new();
         ticker.reload(&self.ticker_command());
@@ -81,3 +93,58 @@ pub trait RedisScrip {
     }
 }
 
+
+fn tickers_pipeline(scrips: &[Scrip]) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    scrips.iter().for_each(|x| { pipeline.add_command(x.ticker_command()); });
+    pipeline
+}
+
+// Tickers of every scrip with a single pipeline of HGETALLs. Scrips missing
+// on Redis get a default ticker.
+#[cfg(not(test))]
+pub fn fetch_tickers(scrips: &[Scrip]) -> redis::RedisResult<HashMap<Scrip, Ticker>> {
+    if scrips.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut connection = crate::utils::pooled_connection()?;
+    let tickers: Vec<Ticker> = tickers_pipeline(scrips).query(&mut *connection)?;
+    Ok(scrips.iter().cloned().zip(tickers).collect())
+}
+
+#[cfg(test)]
+pub fn fetch_tickers(scrips: &[Scrip]) -> redis::RedisResult<HashMap<Scrip, Ticker>> {
+    use crate::test_util::TEST_TICKER_1;
+    Ok(scrips.iter().map(|x| (x.clone(), TEST_TICKER_1.clone())).collect())
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::redis_utils::*;
+    use redis::Value;
+
+    #[test]
+    fn pipelined_fetch() {
+        let a = Scrip::Stock(StockScrip::new("A", "NSE", "C"));
+        let b = Scrip::Stock(StockScrip::new("B", "NSE", "C"));
+        let packed = tickers_pipeline(&[a.clone(), b.clone()]).get_packed_pipeline();
+        let expected = [a.ticker_command().get_packed_command(), b.ticker_command().get_packed_command()].concat();
+        assert_eq!(packed, expected);
+
+        // Replies come back in order, with an empty hash for missing scrips.
+        let reply = Value::Bulk(vec![
+            Value::Bulk(vec![Value::Data(b"ltp".to_vec()), Value::Data(b"400.5".to_vec())]),
+            Value::Bulk(vec![]),
+        ]);
+        let tickers: Vec<Ticker> = redis::from_redis_value(&reply).unwrap();
+        assert_eq!(tickers[0].ltp, 400.5);
+        assert_eq!(tickers[1], Ticker::new());
+        assert!(redis::from_redis_value::<Ticker>(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::scrip::Scrip;
+use crate::position::{Position, Transaction};
+use crate::lots::{LotBook, LotPolicy, ClosedLot};
+use crate::redis_utils::RedisScrip;
+use chrono::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+
+// =============================================================================
+//                            Trade Classification
+// =============================================================================
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TradeClass {
+    Intraday,
+    ShortTermCapitalGain,
+    LongTermCapitalGain,
+    FnoBusinessIncome,
+}
+
+impl std::fmt::Display for TradeClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Equity delivery held for more than a year is long term.
+const LONG_TERM_DAYS: i64 = 365;
+
+impl TradeClass {
+    pub fn of(lot: &ClosedLot) -> Self {
+        match lot.scrip {
+            Scrip::Option(_) => TradeClass::FnoBusinessIncome,
+            Scrip::Stock(_) | Scrip::Index(_) => {
+                if lot.is_intraday() {
+                    TradeClass::Intraday
+                } else if lot.holding_period().num_days() > LONG_TERM_DAYS {
+                    TradeClass::LongTermCapitalGain
+                } else {
+                    TradeClass::ShortTermCapitalGain
+                }
+            }
+        }
+    }
+}
+
+// Flat brokerage per executed order plus a rate on turnover for each class.
+#[derive(Clone, Debug, Default)]
+pub struct ChargeModel {
+    pub brokerage_per_order: f64,
+    pub turnover_rate: HashMap<TradeClass, f64>,
+}
+
+impl ChargeModel {
+    // `orders` can be fractional when an order is shared between trades.
+    pub fn charges(&self, class: TradeClass, turnover: f64, orders: f64) -> f64 {
+        orders * self.brokerage_per_order
+            + turnover * self.turnover_rate.get(&class).copied().unwrap_or(0.0)
+    }
+}
+
+// =============================================================================
+//                                Trade Report
+// =============================================================================
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trade {
+    pub scrip: String,
+    pub class: TradeClass,
+    pub quantity: i32,
+    pub open_time: DateTime<Local>,
+    pub close_time: DateTime<Local>,
+    pub open_price: f64,
+    pub close_price: f64,
+    pub gross: f64,
+    pub charges: f64,
+    pub net: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub trades: usize,
+    pub gross: f64,
+    pub charges: f64,
+    pub net: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Period {
+    Daily,
+    Monthly,
+    FinancialYear,
+}
+
+impl Period {
+    pub fn label(&self, date: NaiveDate) -> String {
+        match self {
+            Period::Daily => date.format("%Y-%m-%d").to_string(),
+            Period::Monthly => date.format("%Y-%m").to_string(),
+            Period::FinancialYear => {
+                // Financial year runs from April to March.
+                let start = if date.month() >= 4 { date.year() } else { date.year() - 1 };
+                format!("FY{}-{:02}", start, (start + 1) % 100)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TradeReport {
+    pub trades: Vec<Trade>,
+}
+
+impl TradeReport {
+    // Matches buys and sells into round trips. The buys and sells of a scrip
+    // on the same day are netted against each other first, as intraday
+    // trades, and what is left is matched against earlier holdings in FIFO
+    // order. Brokerage is charged once per order and split between the trades
+    // it is part of by quantity.
+    pub fn from_history(history: &[Transaction], charge_model: &ChargeModel) -> Self {
+        let mut sorted: Vec<&Transaction> = history.iter().collect();
+        sorted.sort_by_key(|x| x.exec_time);
+
+        let mut order_quantity: HashMap<(String, DateTime<Local>), i32> = HashMap::new();
+        sorted.iter().for_each(|t| {
+            *order_quantity.entry((t.scrip.key(), t.exec_time)).or_insert(0) += t.quantity.abs();
+        });
+        // A lot without a matching order is charged as an order of its own.
+        let share = |lot: &ClosedLot, time: DateTime<Local>| {
+            match (lot.quantity, order_quantity.get(&(lot.scrip.key(), time))) {
+                (0, _) => 0.0,
+                (_, Some(quantity)) if *quantity > 0 => lot.quantity.abs() as f64 / *quantity as f64,
+                _ => 1.0,
+            }
+        };
+
+        let trades = Self::round_trips(&sorted)
+            .iter()
+            .map(|lot| {
+                let class = TradeClass::of(lot);
+                let turnover = (lot.quantity.abs() as f64) * (lot.open_price + lot.close_price);
+                let orders = share(lot, lot.open_time) + share(lot, lot.close_time);
+                let charges = charge_model.charges(class, turnover, orders);
+                Trade {
+                    scrip: lot.scrip.key(),
+                    class,
+                    quantity: lot.quantity,
+                    open_time: lot.open_time,
+                    close_time: lot.close_time,
+                    open_price: lot.open_price,
+                    close_price: lot.close_price,
+                    gross: lot.realized,
+                    charges,
+                    net: lot.realized - charges,
+                }
+            })
+            .collect();
+
+        Self { trades }
+    }
+
+    // Closed lots from transactions sorted by time, netting each day first.
+    fn round_trips(sorted: &[&Transaction]) -> Vec<ClosedLot> {
+        let mut days: BTreeMap<NaiveDate, Vec<&Transaction>> = BTreeMap::new();
+        sorted.iter().for_each(|t| days.entry(t.exec_time.date_naive()).or_default().push(t));
+
+        let mut delivery = LotBook::new(LotPolicy::Fifo);
+        let mut closed: Vec<ClosedLot> = Vec::new();
+        for transactions in days.values() {
+            let mut intraday = LotBook::new(LotPolicy::Fifo);
+            transactions.iter().for_each(|t| intraday.add_transaction(t));
+            closed.append(&mut intraday.closed);
+
+            let mut carried: Vec<Transaction> = intraday.open
+                .into_iter()
+                .flat_map(|(scrip, lots)| {
+                    lots.into_iter().map(move |x| Transaction {
+                        scrip: scrip.clone(),
+                        quantity: x.quantity,
+                        avg_price: x.price,
+                        exec_time: x.open_time,
+                    })
+                })
+                .collect();
+            carried.sort_by_key(|x| x.exec_time);
+            carried.iter().for_each(|t| delivery.add_transaction(t));
+        }
+
+        closed.append(&mut delivery.closed);
+        closed.sort_by_key(|x| x.close_time);
+        closed
+    }
+
+    // Totals per period (by close date) and trade class.
+    pub fn summary(&self, period: Period) -> BTreeMap<(String, TradeClass), Summary> {
+        let mut summary: BTreeMap<(String, TradeClass), Summary> = BTreeMap::new();
+        self.trades.iter().for_each(|x| {
+            let label = period.label(x.close_time.date_naive());
+            let entry = summary.entry((label, x.class)).or_default();
+            entry.trades += 1;
+            entry.gross += x.gross;
+            entry.charges += x.charges;
+            entry.net += x.net;
+        });
+        summary
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "scrip,class,quantity,open_time,close_time,open_price,close_price,gross,charges,net\n"
+        );
+        self.trades.iter().for_each(|x| {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{}\n",
+                x.scrip, x.class, x.quantity, x.open_time.to_rfc3339(), x.close_time.to_rfc3339(),
+                x.open_price, x.close_price, x.gross, x.charges, x.net,
+            ));
+        });
+        csv
+    }
+
+    pub fn summary_to_csv(&self, period: Period) -> String {
+        let mut csv = String::from("period,class,trades,gross,charges,net\n");
+        self.summary(period).iter().for_each(|((label, class), x)| {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                label, class, x.trades, x.gross, x.charges, x.net,
+            ));
+        });
+        csv
+    }
+}
+
+impl Position {
+    pub fn trade_report(&self, charge_model: &ChargeModel) -> TradeReport {
+        TradeReport::from_history(&self.history, charge_model)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::report::*;
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    fn transaction(scrip: &Scrip, quantity: i32, avg_price: f64, date: (i32, u32, u32), hour: u32) -> Transaction {
+        Transaction {
+            scrip: scrip.clone(),
+            quantity,
+            avg_price,
+            exec_time: Local.with_ymd_and_hms(date.0, date.1, date.2, hour, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn classification() {
+        let stock = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let history = vec![
+            transaction(&stock, 10, 100.0, (2021, 1, 4), 10),
+            transaction(&stock, 10, 100.0, (2022, 3, 1), 10),
+            transaction(&stock, -10, 110.0, (2022, 3, 1), 11),
+            transaction(&stock, -10, 120.0, (2022, 4, 1), 11),
+        ];
+        let charges = ChargeModel { brokerage_per_order: 10.0, ..Default::default() };
+        let report = TradeReport::from_history(&history, &charges);
+
+        // The 2022-03-01 sell nets against that day's buy, not the 2021 lot.
+        let classes: Vec<TradeClass> = report.trades.iter().map(|x| x.class).collect();
+        assert_eq!(classes, vec![TradeClass::Intraday, TradeClass::LongTermCapitalGain]);
+        assert_eq!(report.trades[0].net, 80.0);
+
+        let summary = report.summary(Period::FinancialYear);
+        assert_eq!(summary[&("FY2021-22".to_string(), TradeClass::Intraday)].net, 80.0);
+        assert_eq!(summary[&("FY2022-23".to_string(), TradeClass::LongTermCapitalGain)].gross, 200.0);
+    }
+
+    #[test]
+    fn brokerage_per_order() {
+        let stock = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let history = vec![
+            transaction(&stock, 20, 100.0, (2022, 6, 1), 10),
+            transaction(&stock, -10, 110.0, (2022, 6, 2), 10),
+            transaction(&stock, -10, 110.0, (2022, 6, 3), 10),
+        ];
+        let charges = ChargeModel { brokerage_per_order: 10.0, ..Default::default() };
+        let report = TradeReport::from_history(&history, &charges);
+
+        // Three orders, with the buy shared between both trades.
+        assert_eq!(report.trades.iter().map(|x| x.charges).sum::<f64>(), 30.0);
+        assert_eq!(report.trades[0].charges, 15.0);
+    }
+
+    #[test]
+    fn empty_orders_not_charged() {
+        let stock = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let history = vec![
+            transaction(&stock, 0, 100.0, (2022, 6, 1), 9),
+            transaction(&stock, 10, 100.0, (2022, 6, 1), 10),
+            transaction(&stock, 0, 105.0, (2022, 6, 1), 11),
+            transaction(&stock, -10, 110.0, (2022, 6, 1), 11),
+        ];
+        let charges = ChargeModel { brokerage_per_order: 10.0, ..Default::default() };
+        let report = TradeReport::from_history(&history, &charges);
+        assert_eq!(report.trades.iter().map(|x| x.charges).sum::<f64>(), 20.0);
+    }
+
+    #[test]
+    fn financial_year_label() {
+        assert_eq!(Period::FinancialYear.label(NaiveDate::from_ymd_opt(2023, 3, 31).unwrap()), "FY2022-23");
+        assert_eq!(Period::FinancialYear.label(NaiveDate::from_ymd_opt(1999, 4, 1).unwrap()), "FY1999-00");
+    }
+}
@@ -0,0 +1,224 @@
+use crate::scrip::Scrip;
+use crate::redis_utils::RedisScrip;
+use crate::orders::{Order, BasketOrder};
+use crate::position::Position;
+use crate::info::MetaData;
+use crate::provider::MetadataProvider;
+use crate::error::{Error, MetadataError};
+use std::collections::HashMap;
+
+// =============================================================================
+//                              Pre-trade Risk Gate
+// =============================================================================
+
+// Limits that are left as `None` (or scrips missing from `contracts`) are not
+// checked.
+#[derive(Clone, Debug, Default)]
+pub struct RiskGate {
+    pub max_order_value: Option<f64>,
+    // Maximum absolute quantity of a single order in a scrip.
+    pub max_quantity: Option<u32>,
+    // Maximum absolute quantity held in a scrip after the order is filled.
+    pub max_position: Option<u32>,
+    // Allowed deviation of the order price from `ltp`, as a fraction. 0.05
+    // allows orders within 5% of `ltp`.
+    pub price_band: Option<f64>,
+    // Contract specifications orders are validated against, see
+    // `Order::validate`. These carry the freeze quantities.
+    pub contracts: HashMap<Scrip, MetaData>,
+    // Loss (as a positive number) since `start_day` beyond which no new
+    // orders are accepted.
+    pub daily_loss_limit: Option<f64>,
+    // P&L of the position when the day started, see `start_day`.
+    pub start_of_day_pnl: f64,
+}
+
+impl RiskGate {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Sets the baseline the daily loss is measured from. Call it at the start
+    // of every trading day.
+    pub fn start_day(&mut self, position: &Position) -> redis::RedisResult<()> {
+        self.start_of_day_pnl = position.get_pnl()?;
+        Ok(())
+    }
+
+    // Looks up the contract specifications of `scrips` for validation.
+    pub fn load_contracts(&mut self, scrips: &[Scrip], provider: &dyn MetadataProvider) -> Result<(), MetadataError> {
+        for scrip in scrips.iter() {
+            self.contracts.insert(scrip.clone(), scrip.get_contract_metadata_from(provider)?);
+        }
+        Ok(())
+    }
+
+    pub fn check_order(&self, order: &Order, position: &Position) -> Result<(), Error> {
+        let holding = position.holding.get(&order.scrip).map_or(0, |h| h.0);
+        self.check_daily_loss(position)?;
+        self.check_leg(order, holding)
+    }
+
+    // Legs in the same scrip are accumulated while checking the open position
+    // limit.
+    pub fn check_basket(&self, basket: &BasketOrder, position: &Position) -> Result<(), Error> {
+        self.check_daily_loss(position)?;
+
+        let mut holdings: HashMap<Scrip, i32> = HashMap::new();
+        basket.orders.iter().try_for_each(|x| {
+            let holding = holdings
+                .entry(x.scrip.clone())
+                .or_insert_with(|| position.holding.get(&x.scrip).map_or(0, |h| h.0));
+            self.check_leg(x, *holding)?;
+            *holding += x.quantity;
+            Ok(())
+        })
+    }
+
+    fn check_leg(&self, order: &Order, holding: i32) -> Result<(), Error> {
+        let quantity = order.quantity.unsigned_abs();
+
+        if let Some(metadata) = self.contracts.get(&order.scrip) {
+            order.validate(metadata)?;
+        }
+
+        if let Some(max) = self.max_quantity {
+            if quantity > max {
+                return Err(Error::MaxQuantityExceeded(quantity, max));
+            }
+        }
+        if let Some(max) = self.max_position {
+            let resulting = holding + order.quantity;
+            if resulting.unsigned_abs() > max {
+                return Err(Error::MaxPositionExceeded(resulting, max));
+            }
+        }
+
+        if self.max_order_value.is_none() && self.price_band.is_none() {
+            return Ok(());
+        }
+
+        let price = order.margin()?;
+        if let Some(max) = self.max_order_value {
+            let value = price * quantity as f64;
+            if value > max {
+                return Err(Error::MaxOrderValueExceeded(value, max));
+            }
+        }
+        if let Some(band) = self.price_band {
+            let ltp = order.scrip.updated_ticker().ltp;
+            let (lower, upper) = (ltp * (1.0 - band), ltp * (1.0 + band));
+            if price < lower || price > upper {
+                return Err(Error::OutsidePriceBand(price, lower, upper));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_daily_loss(&self, position: &Position) -> Result<(), Error> {
+        match self.daily_loss_limit {
+            Some(limit) => {
+                let pnl = position.get_pnl()? - self.start_of_day_pnl;
+                if pnl < -limit {
+                    return Err(Error::DailyLossLimitBreached(pnl, limit));
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::risk::RiskGate;
+    use crate::error::Error;
+
+    fn test_scrip() -> Scrip {
+        Scrip::Stock(StockScrip::new("TEST", "NSE", "C"))
+    }
+
+    #[test]
+    fn price_band() {
+        let gate = RiskGate { price_band: Some(0.05), ..Default::default() };
+        let position: Position = Default::default();
+        let fat_finger = Order::new(test_scrip(), 1, OrderType::LimitOrder(450.0));
+        let market = Order::new(test_scrip(), 1, OrderType::MarketOrder);
+        assert_eq!(
+            gate.check_order(&fat_finger, &position),
+            Err(Error::OutsidePriceBand(450.0, 400.23 * 0.95, 400.23 * 1.05))
+        );
+        assert_eq!(gate.check_order(&market, &position), Ok(()));
+    }
+
+    #[test]
+    fn basket_open_position() {
+        let gate = RiskGate { max_position: Some(10), ..Default::default() };
+        let position: Position = Default::default();
+        let basket = BasketOrder {
+            basket_order_type: BasketOrderType::AllOrNone,
+            orders: vec![
+                Order::new(test_scrip(), 6, OrderType::MarketOrder),
+                Order::new(test_scrip(), 6, OrderType::MarketOrder),
+            ],
+        };
+        assert_eq!(gate.check_basket(&basket, &position), Err(Error::MaxPositionExceeded(12, 10)));
+    }
+
+    #[test]
+    fn order_value_and_quantity() {
+        let position: Position = Default::default();
+        let order = Order::new(test_scrip(), 10, OrderType::LimitOrder(400.0));
+
+        let gate = RiskGate { max_order_value: Some(3000.0), ..Default::default() };
+        assert_eq!(gate.check_order(&order, &position), Err(Error::MaxOrderValueExceeded(4000.0, 3000.0)));
+
+        let gate = RiskGate { max_quantity: Some(5), ..Default::default() };
+        assert_eq!(gate.check_order(&order, &position), Err(Error::MaxQuantityExceeded(10, 5)));
+
+        let provider = crate::provider::InMemoryProvider::from_json(r#"[
+            {"scrip": "TEST", "type": "cash", "exchange": "nse", "freezeQuantity": 8}
+        ]"#).unwrap();
+        let mut gate: RiskGate = Default::default();
+        gate.load_contracts(&[test_scrip()], &provider).unwrap();
+        assert_eq!(gate.check_order(&order, &position), Err(Error::FreezeQuantityExceeded(10, 8)));
+        assert_eq!(order.execute(&gate, &position).err(), Some(Error::FreezeQuantityExceeded(10, 8)));
+    }
+
+    #[test]
+    fn contract_specs() {
+        use crate::provider::InMemoryProvider;
+        let provider = InMemoryProvider::from_json(r#"[
+            {"scrip": "TEST", "type": "cash", "exchange": "nse", "lotSize": 5, "tickSize": 0.05}
+        ]"#).unwrap();
+        let mut gate: RiskGate = Default::default();
+        gate.load_contracts(&[test_scrip()], &provider).unwrap();
+        let position: Position = Default::default();
+
+        let order = Order::new(test_scrip(), 8, OrderType::MarketOrder);
+        assert_eq!(gate.check_order(&order, &position), Err(Error::NotLotMultiple(8, 5)));
+        assert_eq!(order.execute(&gate, &position).err(), Some(Error::NotLotMultiple(8, 5)));
+        assert_eq!(gate.check_order(&Order::new(test_scrip(), 10, OrderType::MarketOrder), &position), Ok(()));
+    }
+
+    #[test]
+    fn daily_loss() {
+        let mut position: Position = Default::default();
+        position.add_transaction(Order::new(test_scrip(), 10, OrderType::LimitOrder(500.0)).execute_unchecked().unwrap());
+        let order = Order::new(test_scrip(), 1, OrderType::MarketOrder);
+
+        // Bought at 500 with the ltp at 400.23.
+        let mut gate = RiskGate { daily_loss_limit: Some(500.0), ..Default::default() };
+        assert!(matches!(gate.check_order(&order, &position), Err(Error::DailyLossLimitBreached(_, _))));
+
+        // Losses from before the day started do not count.
+        gate.start_day(&position).unwrap();
+        assert_eq!(gate.check_order(&order, &position), Ok(()));
+    }
+}
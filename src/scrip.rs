@@ -1,17 +1,16 @@
-use aws_sdk_dynamodb::model::AttributeValue;
-use aws_types::sdk_config::SdkConfig;
-use aws_sdk_dynamodb::Client;
 use crate::tickers::Ticker;
 use crate::options::EXPIRY_FORMAT;
 use crate::{IndexScrip, StockScrip, OptionScrip, OptionType};
 use crate::redis_utils::RedisScrip;
-use crate::info::{MetaData, TABLE_NAME};
+use crate::info::MetaData;
+use crate::provider::{MetadataProvider, metadata_provider};
+use crate::error::MetadataError;
 use chrono::NaiveDate;
 use redis;
 use std::hash::{Hash, Hasher};
-use cached::proc_macro::cached;
+use serde::{Serialize, Deserialize};
 
-#[derive(Copy, Debug, Clone)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Exchange {
     NSE,
     BSE,
@@ -39,7 +38,7 @@ impl ToString for Exchange {
     }
 }
 
-#[derive(Copy, Debug, Clone)]
+#[derive(Copy, Debug, Clone, Serialize, Deserialize)]
 pub enum ExchangeType {
     Cash,
     Index,
@@ -83,35 +82,7 @@ impl RedisScrip for RawScrip {
     }
 }
 
-#[cached]
-fn dynamo_call(name: String) -> Option<MetaData> {
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build().unwrap();
-
-    let shared_config: SdkConfig = runtime.block_on(aws_config::load_from_env());
-    let client = Client::new(&shared_config);
-
-    let request = client
-        .get_item()
-        .table_name(TABLE_NAME)
-        .key(
-            "scrip",
-            AttributeValue::S(name),
-        );
-
-    let response = runtime.block_on(request.send());
-    match response {
-        Ok(r) => {
-            Some(MetaData::from_response(r.item.as_ref().unwrap()))
-        },
-        Err(_) => {
-            None
-        }
-    }
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Scrip {
     Stock(StockScrip),
     Index(IndexScrip),
@@ -162,14 +133,49 @@ impl Scrip {
         }
     }
 
-    pub fn get_metadata(&self) -> Option<MetaData> {
-        let name = match self {
+    pub fn name(&self) -> String {
+        match self {
             Scrip::Stock(stock) => stock.name.clone(),
             Scrip::Index(index) => index.name.clone(),
             Scrip::Option(option) => option.name.clone(),
-        };
+        }
+    }
 
-        dynamo_call(name)
+    // Goes through the provider set with `set_metadata_provider`, falling back
+    // to a cached lookup on the `scrip_info` table on DynamoDB. Options get
+    // the metadata of their underlying, see `get_contract_metadata`.
+    pub fn get_metadata(&self) -> Result<MetaData, MetadataError> {
+        self.get_metadata_from(&*metadata_provider()?)
+    }
+
+    // Drops the cached metadata so that the next lookup fetches it again.
+    pub fn invalidate_metadata(&self) -> Result<(), MetadataError> {
+        let provider = metadata_provider()?;
+        provider.invalidate(&self.name());
+        provider.invalidate(&self.metadata_name());
+        Ok(())
+    }
+
+    pub fn get_metadata_from(&self, provider: &dyn MetadataProvider) -> Result<MetaData, MetadataError> {
+        provider.get(&self.name())
+    }
+
+    // Metadata of the traded contract itself, which for options is stored
+    // under the option's key rather than the underlying's name.
+    pub fn get_contract_metadata(&self) -> Result<MetaData, MetadataError> {
+        self.get_contract_metadata_from(&*metadata_provider()?)
+    }
+
+    pub fn get_contract_metadata_from(&self, provider: &dyn MetadataProvider) -> Result<MetaData, MetadataError> {
+        provider.get(&self.metadata_name())
+    }
+
+    // Name the contract metadata is stored under.
+    pub fn metadata_name(&self) -> String {
+        match self {
+            Scrip::Option(o) => o.key(),
+            _ => self.name(),
+        }
     }
 }
 
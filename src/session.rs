@@ -0,0 +1,270 @@
+use crate::scrip::Exchange;
+use crate::info::{MetaData, parse_exchange};
+use crate::error::MetadataError;
+use crate::calendar::TradingCalendar;
+use chrono::prelude::*;
+use chrono::Duration;
+
+// =============================================================================
+//                               Market Sessions
+// =============================================================================
+
+// Days to look ahead for the next open/close before giving up.
+const LOOKAHEAD_DAYS: i64 = 14;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SessionPhase {
+    PreOpen,
+    Continuous,
+    ClosingAuction,
+    // MCX's evening session, which continues after the equity markets close.
+    Evening,
+}
+
+impl SessionPhase {
+    // Phases in which regular orders are matched.
+    pub fn is_trading(&self) -> bool {
+        matches!(self, SessionPhase::Continuous | SessionPhase::Evening)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhaseWindow {
+    pub phase: SessionPhase,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl PhaseWindow {
+    pub fn new(phase: SessionPhase, start: (u32, u32), end: (u32, u32)) -> Self {
+        Self {
+            phase,
+            start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MarketSession {
+    pub exchange: Exchange,
+    pub timezone: FixedOffset,
+    // Sorted by start, within a single day.
+    pub phases: Vec<PhaseWindow>,
+    pub calendar: Option<TradingCalendar>,
+}
+
+impl MarketSession {
+    // Regular sessions in IST.
+    // NSE/BSE: pre-open 09:00-09:15, continuous 09:15-15:30 and the closing
+    //          session 15:40-16:00.
+    // MCX:     continuous 09:00-17:00 followed by the evening session till 23:30.
+    pub fn for_exchange(exchange: Exchange) -> Self {
+        let phases = match exchange {
+            Exchange::NSE | Exchange::BSE => vec![
+                PhaseWindow::new(SessionPhase::PreOpen, (9, 0), (9, 15)),
+                PhaseWindow::new(SessionPhase::Continuous, (9, 15), (15, 30)),
+                PhaseWindow::new(SessionPhase::ClosingAuction, (15, 40), (16, 0)),
+            ],
+            Exchange::MCX => vec![
+                PhaseWindow::new(SessionPhase::Continuous, (9, 0), (17, 0)),
+                PhaseWindow::new(SessionPhase::Evening, (17, 0), (23, 30)),
+            ],
+        };
+
+        Self {
+            exchange,
+            timezone: FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap(),
+            phases,
+            calendar: None,
+        }
+    }
+
+    // Holidays and special sessions from the calendar override the regular
+    // weekday sessions.
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    // Exchange defaults, with the timezone and the continuous session taken
+    // from the metadata's open and close times. The continuous session is
+    // clamped to the phases around it, so an exchange close time such as
+    // MCX's 23:30 does not run over the evening session.
+    pub fn from_metadata(metadata: &MetaData) -> Result<Self, MetadataError> {
+        let exchange = parse_exchange(metadata.exchange())?;
+
+        let mut session = Self::for_exchange(exchange);
+        session.timezone = metadata.timezone();
+        for i in 0..session.phases.len() {
+            if session.phases[i].phase != SessionPhase::Continuous {
+                continue;
+            }
+            let earliest = i.checked_sub(1).map(|x| session.phases[x].end);
+            let latest = session.phases.get(i + 1).map(|x| x.start);
+
+            let window = &mut session.phases[i];
+            window.start = metadata.open_time().unwrap_or(window.start);
+            window.end = metadata.close_time().unwrap_or(window.end);
+            if let Some(earliest) = earliest {
+                window.start = window.start.max(earliest);
+            }
+            if let Some(latest) = latest {
+                window.end = window.end.min(latest);
+            }
+        }
+        Ok(session)
+    }
+
+    // Without a calendar only weekends are closed.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        match &self.calendar {
+            Some(calendar) => calendar.is_trading_day(date),
+            None => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+        }
+    }
+
+    // Phases on a trading day, taking special sessions into account.
+    pub fn phases_on(&self, date: NaiveDate) -> &[PhaseWindow] {
+        match self.calendar.as_ref().and_then(|x| x.special_session(date)) {
+            Some(special) => &special.phases,
+            None => &self.phases,
+        }
+    }
+
+    pub fn phase_at(&self, now: DateTime<Utc>) -> Option<SessionPhase> {
+        let local = now.with_timezone(&self.timezone);
+        if !self.is_trading_day(local.date_naive()) {
+            return None;
+        }
+
+        let time = local.time();
+        self.phases_on(local.date_naive())
+            .iter()
+            .find(|x| x.start <= time && time < x.end)
+            .map(|x| x.phase)
+    }
+
+    pub fn is_market_open(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.phase_at(now), Some(x) if x.is_trading())
+    }
+
+    // Consecutive trading phases merged into (start, end) blocks.
+    fn trading_blocks(phases: &[PhaseWindow]) -> Vec<(NaiveTime, NaiveTime)> {
+        let mut blocks: Vec<(NaiveTime, NaiveTime)> = Vec::new();
+        phases.iter().filter(|x| x.phase.is_trading()).for_each(|x| {
+            match blocks.last_mut() {
+                Some(last) if last.1 == x.start => last.1 = x.end,
+                _ => blocks.push((x.start, x.end)),
+            }
+        });
+        blocks
+    }
+
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        self.timezone
+            .from_local_datetime(&date.and_time(time))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    // Trading blocks as UTC (start, end), from the day of `now` onwards.
+    fn upcoming_blocks(&self, now: DateTime<Utc>) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> + '_ {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        (0..LOOKAHEAD_DAYS)
+            .map(move |x| today + Duration::days(x))
+            .filter(move |x| self.is_trading_day(*x))
+            .flat_map(move |date| {
+                Self::trading_blocks(self.phases_on(date))
+                    .into_iter()
+                    .map(move |(start, end)| (self.to_utc(date, start), self.to_utc(date, end)))
+            })
+    }
+
+    // End of the last trading block on `date`, if it is a trading day.
+    pub fn close_on(&self, date: NaiveDate) -> Option<DateTime<Utc>> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        Self::trading_blocks(self.phases_on(date))
+            .last()
+            .map(|x| self.to_utc(date, x.1))
+    }
+
+    // Start of the next trading block strictly after `now`.
+    pub fn next_open(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.upcoming_blocks(now).map(|x| x.0).find(|x| *x > now)
+    }
+
+    // End of the current trading block, or of the next one if closed.
+    pub fn next_close(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.upcoming_blocks(now).map(|x| x.1).find(|x| *x > now)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Exchange;
+    use crate::session::*;
+    use crate::calendar::{TradingCalendar, SpecialSession};
+
+    // 2022-06-03 is a Friday.
+    fn ist(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        FixedOffset::east_opt(19800).unwrap()
+            .with_ymd_and_hms(2022, 6, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn nse_phases() {
+        let nse = MarketSession::for_exchange(Exchange::NSE);
+        assert_eq!(nse.phase_at(ist(3, 9, 5)), Some(SessionPhase::PreOpen));
+        assert!(nse.is_market_open(ist(3, 15, 29)));
+        assert_eq!(nse.phase_at(ist(3, 15, 35)), None);
+        assert_eq!(nse.phase_at(ist(4, 10, 0)), None);
+        assert_eq!(nse.next_open(ist(3, 16, 0)), Some(ist(6, 9, 15)));
+        assert_eq!(nse.next_close(ist(3, 10, 0)), Some(ist(3, 15, 30)));
+    }
+
+    #[test]
+    fn mcx_evening() {
+        let mcx = MarketSession::for_exchange(Exchange::MCX);
+        assert_eq!(mcx.phase_at(ist(3, 20, 0)), Some(SessionPhase::Evening));
+        assert_eq!(mcx.next_close(ist(3, 10, 0)), Some(ist(3, 23, 30)));
+    }
+
+    #[test]
+    fn metadata_clamped_to_evening() {
+        use crate::provider::{InMemoryProvider, MetadataProvider};
+        let provider = InMemoryProvider::from_csv(
+            "scrip,type,exchange,openTime,closeTime,timezone\nGOLD,cash,MCX,0900,2330,+05:30\n"
+        ).unwrap();
+
+        let mcx = MarketSession::from_metadata(&provider.get("GOLD").unwrap()).unwrap();
+        assert_eq!(mcx.phases[0].end, NaiveTime::from_hms_opt(17, 0, 0).unwrap());
+        assert_eq!(mcx.phase_at(ist(3, 20, 0)), Some(SessionPhase::Evening));
+        assert_eq!(mcx.next_close(ist(3, 10, 0)), Some(ist(3, 23, 30)));
+    }
+
+    #[test]
+    fn calendar_sessions() {
+        let mut calendar = TradingCalendar::new(Exchange::NSE);
+        calendar.add_holiday(NaiveDate::from_ymd_opt(2022, 6, 6).unwrap(), "Holiday");
+        calendar.add_special_session(SpecialSession {
+            date: NaiveDate::from_ymd_opt(2022, 6, 5).unwrap(),
+            description: String::from("Muhurat"),
+            phases: vec![PhaseWindow::new(SessionPhase::Continuous, (18, 15), (19, 15))],
+        });
+
+        let nse = MarketSession::for_exchange(Exchange::NSE).with_calendar(calendar);
+        assert!(nse.is_market_open(ist(5, 18, 30)));
+        assert!(!nse.is_market_open(ist(6, 10, 0)));
+        assert_eq!(nse.next_open(ist(3, 16, 0)), Some(ist(5, 18, 15)));
+        assert_eq!(nse.next_open(ist(5, 20, 0)), Some(ist(7, 9, 15)));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::scrip::Scrip;
+use crate::options::{OptionScrip, OptionType};
+use crate::position::{Position, Transaction};
+use crate::pricing::expiry_close;
+use crate::session::MarketSession;
+use chrono::prelude::*;
+use std::collections::HashMap;
+
+// =============================================================================
+//                             Expiry Settlement
+// =============================================================================
+
+// Options on stocks are settled by delivery of the underlying, everything else
+// is cash settled.
+fn is_physically_settled(option: &OptionScrip) -> bool {
+    matches!(option.underlying.as_deref(), Some(Scrip::Stock(_)))
+}
+
+fn intrinsic_value(option: &OptionScrip, settlement_price: f64) -> f64 {
+    let strike = option.strike as f64;
+    match option.option_type {
+        OptionType::CE => (settlement_price - strike).max(0.0),
+        OptionType::PE => (strike - settlement_price).max(0.0),
+    }
+}
+
+// Transactions that settle `quantity` of an expired option.
+// - OTM options are closed at zero.
+// - ITM options are closed at their intrinsic value, realizing it.
+// - For ITM physically settled options the underlying is also delivered at
+//   the settlement price, so its cost basis carries no option P&L.
+pub fn settlement_transactions(
+    option: &OptionScrip,
+    quantity: i32,
+    settlement_price: f64,
+    exec_time: DateTime<Local>,
+) -> Vec<Transaction> {
+    let intrinsic = intrinsic_value(option, settlement_price);
+    let scrip = Scrip::Option(option.clone());
+
+    if intrinsic > 0.0 && is_physically_settled(option) {
+        let delivered = match option.option_type {
+            OptionType::CE => quantity,
+            OptionType::PE => -quantity,
+        };
+        return vec![
+            Transaction { scrip, quantity: -quantity, avg_price: intrinsic, exec_time },
+            Transaction {
+                scrip: *option.underlying.clone().unwrap(),
+                quantity: delivered,
+                avg_price: settlement_price,
+                exec_time,
+            },
+        ];
+    }
+
+    vec![Transaction { scrip, quantity: -quantity, avg_price: intrinsic, exec_time }]
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Settlement {
+    pub transactions: Vec<Transaction>,
+    // Expired options left open for want of a settlement price.
+    pub unpriced: Vec<Scrip>,
+}
+
+impl Position {
+    // Settles every option holding whose expiry session has closed by `now`,
+    // at the close of trading on the expiry day.
+    // The settlement price of the underlying is looked up in
+    // `settlement_prices` (keyed by the underlying scrip), or keyed by the
+    // option itself for options without an underlying. Options without a
+    // price stay open and are returned as unpriced. The generated
+    // transactions are added to the position, moving the P&L into realized.
+    pub fn settle_expired(
+        &mut self,
+        now: DateTime<Local>,
+        settlement_prices: &HashMap<Scrip, f64>,
+    ) -> Settlement {
+        let expired: Vec<(OptionScrip, i32, DateTime<Local>)> = self.holding
+            .iter()
+            .filter_map(|(s, (q, _))| match s {
+                Scrip::Option(o) => {
+                    let session = MarketSession::for_exchange(o.exchange);
+                    let close = expiry_close(o.expiry, &session).with_timezone(&Local);
+                    (close <= now).then(|| (o.clone(), *q, close))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut settlement: Settlement = Default::default();
+        for (option, quantity, exec_time) in expired {
+            let reference = match &option.underlying {
+                Some(u) => *u.clone(),
+                None => Scrip::Option(option.clone()),
+            };
+            match settlement_prices.get(&reference) {
+                Some(price) => settlement.transactions.extend(
+                    settlement_transactions(&option, quantity, *price, exec_time)
+                ),
+                None => settlement.unpriced.push(Scrip::Option(option)),
+            }
+        }
+
+        settlement.transactions.iter().for_each(|t| self.add_transaction(t.clone()));
+        settlement
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use chrono::prelude::*;
+    use std::collections::HashMap;
+
+    fn expiry() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, 30).unwrap()
+    }
+
+    // Time on the expiry day in IST.
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        FixedOffset::east_opt(19800).unwrap()
+            .with_ymd_and_hms(2022, 6, 30, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    #[test]
+    fn cash_settled_index_option() {
+        let index = Scrip::Index(IndexScrip::new("NIFTY", "NSE", "I"));
+        let call = Scrip::Option(OptionScrip::new("NIFTY", "NSE", "O", expiry(), 15000, OptionType::CE, Some(index.clone())));
+        let put = Scrip::Option(OptionScrip::new("NIFTY", "NSE", "O", expiry(), 15000, OptionType::PE, Some(index.clone())));
+
+        let mut position: Position = Default::default();
+        position.update_holding(call, 50, 100.0);
+        position.update_holding(put, -50, 80.0);
+
+        let prices = HashMap::from([(index, 15250.0)]);
+        let settlement = position.settle_expired(at(16, 0), &prices);
+        assert_eq!(settlement.transactions.len(), 2);
+        assert_eq!(settlement.transactions[0].exec_time, at(15, 30));
+        assert!(position.holding.is_empty());
+        assert_eq!(position.realized_pnl(), 50.0 * 150.0 + 50.0 * 80.0);
+    }
+
+    #[test]
+    fn physically_settled_stock_option() {
+        let stock = Scrip::Stock(StockScrip::new("SBIN", "NSE", "C"));
+        let put = Scrip::Option(OptionScrip::new("SBIN", "NSE", "O", expiry(), 500, OptionType::PE, Some(stock.clone())));
+
+        let mut position: Position = Default::default();
+        position.update_holding(put, -1500, 12.0);
+
+        // Assigned on a put with 20 of intrinsic value, sold at 12.
+        let prices = HashMap::from([(stock.clone(), 480.0)]);
+        position.settle_expired(at(16, 0), &prices);
+        assert_eq!(position.holding.get(&stock), Some(&(1500, 480.0)));
+        assert_eq!(position.realized_pnl(), 1500.0 * (12.0 - 20.0));
+    }
+
+    #[test]
+    fn physically_settled_call_realized() {
+        let stock = Scrip::Stock(StockScrip::new("SBIN", "NSE", "C"));
+        let call = Scrip::Option(OptionScrip::new("SBIN", "NSE", "O", expiry(), 500, OptionType::CE, Some(stock.clone())));
+
+        let mut position: Position = Default::default();
+        position.update_holding(call, 1500, 10.0);
+
+        let prices = HashMap::from([(stock.clone(), 530.0)]);
+        position.settle_expired(at(16, 0), &prices);
+        assert_eq!(position.holding.get(&stock), Some(&(1500, 530.0)));
+        assert_eq!(position.realized_pnl(), 1500.0 * (30.0 - 10.0));
+    }
+
+    #[test]
+    fn unexpired_options_untouched() {
+        let index = Scrip::Index(IndexScrip::new("NIFTY", "NSE", "I"));
+        let call = Scrip::Option(OptionScrip::new("NIFTY", "NSE", "O", expiry(), 15000, OptionType::CE, Some(index.clone())));
+
+        let mut position: Position = Default::default();
+        position.update_holding(call.clone(), 50, 100.0);
+        let prices = HashMap::from([(index, 15250.0)]);
+        let settlement = position.settle_expired(at(15, 0), &prices);
+        assert!(settlement.transactions.is_empty());
+        assert_eq!(position.holding.len(), 1);
+
+        let settlement = position.settle_expired(at(16, 0), &HashMap::new());
+        assert!(settlement.transactions.is_empty());
+        assert_eq!(settlement.unpriced, vec![call]);
+        assert_eq!(position.holding.len(), 1);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::redis_utils::RedisScrip;
 use crate::scrip::{Exchange, ExchangeType};
+use serde::{Serialize, Deserialize};
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StockScrip {
     pub name: String,
     pub exchange: Exchange,
@@ -0,0 +1,258 @@
+use crate::scrip::Scrip;
+use crate::tickers::{CompleteTicker, Ticker};
+use crate::redis_utils::RedisScrip;
+use crate::utils::{CLIENT, StopSignal};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// =============================================================================
+//                             Ticker Subscriptions
+// =============================================================================
+
+// How often the listener wakes up to check whether it was stopped.
+const POLL_INTERVAL_MS: u64 = 500;
+const MIN_RECONNECT_DELAY_MS: u64 = 100;
+const MAX_RECONNECT_DELAY_MS: u64 = 10_000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UpdateSource {
+    // Keyspace notifications on the ticker hashes, in the given database.
+    // Needs `notify-keyspace-events` to include `Kh` on the server.
+    Keyspace(i64),
+    // Messages on each scrip's `updates_channel`, published by the feed.
+    Channel,
+}
+
+impl UpdateSource {
+    pub fn channel(&self, scrip: &Scrip) -> String {
+        match self {
+            UpdateSource::Keyspace(db) => format!("__keyspace@{}__:{}", db, scrip.key()),
+            UpdateSource::Channel => scrip.updates_channel(),
+        }
+    }
+
+    // Whether a message with `payload` signals an update. Keyspace messages
+    // carry the command, and a ticker write is a DEL followed by an HSET.
+    pub fn is_update(&self, payload: &str) -> bool {
+        match self {
+            UpdateSource::Keyspace(_) => payload == "hset",
+            UpdateSource::Channel => true,
+        }
+    }
+}
+
+// Exponential reconnect delay, reset once a connection is established.
+struct Backoff {
+    min: Duration,
+    max: Duration,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new(min: Duration, max: Duration) -> Self {
+        Self { min, max, delay: min }
+    }
+
+    fn reset(&mut self) {
+        self.delay = self.min;
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(self.max);
+        delay
+    }
+}
+
+// Calls `listen` until it returns `Ok` or `stop` is set, waiting out the
+// backoff after every error.
+fn retry<L>(stop: &StopSignal, mut backoff: Backoff, mut listen: L)
+where
+    L: FnMut(&mut Backoff) -> redis::RedisResult<()>,
+{
+    while !stop.is_set() {
+        match listen(&mut backoff) {
+            Ok(()) => return,
+            Err(_) => {
+                if stop.wait(backoff.next_delay()) {
+                    return;
+                }
+            },
+        }
+    }
+}
+
+// Listens on a background thread until stopped or dropped, or until the
+// receiving end of the channel is dropped. Lost connections are retried with
+// an exponential backoff, and every scrip is sent once after (re)subscribing
+// so that no update is missed in between.
+pub struct Subscription {
+    stop: Arc<StopSignal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Subscription {
+    pub fn new<F>(scrips: Vec<Scrip>, source: UpdateSource, send: F) -> Self
+    where
+        F: Fn(CompleteTicker) -> bool + Send + 'static,
+    {
+        let stop: Arc<StopSignal> = Default::default();
+        let channels: HashMap<String, Scrip> = scrips
+            .into_iter()
+            .map(|x| (source.channel(&x), x))
+            .collect();
+
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let backoff = Backoff::new(
+                Duration::from_millis(MIN_RECONNECT_DELAY_MS),
+                Duration::from_millis(MAX_RECONNECT_DELAY_MS),
+            );
+            retry(&stopped, backoff, |backoff| listen(&channels, source, &stopped, &send, backoff));
+        });
+
+        Self { stop, handle: Some(handle) }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.set();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn fetch(connection: &mut redis::Connection, scrip: &Scrip) -> redis::RedisResult<CompleteTicker> {
+    let ticker: Ticker = scrip.ticker_command().query(connection)?;
+    Ok(CompleteTicker { ticker, scrip: scrip.clone() })
+}
+
+// Returns `Ok` once stopped or once `send` fails, and the error if the
+// connection breaks.
+fn listen<F>(channels: &HashMap<String, Scrip>, source: UpdateSource, stop: &StopSignal, send: &F, backoff: &mut Backoff) -> redis::RedisResult<()>
+where
+    F: Fn(CompleteTicker) -> bool,
+{
+    // Subscribed connections cannot run commands, so tickers are read on a
+    // second one.
+    let mut reader = CLIENT.get_connection()?;
+    let mut connection = CLIENT.get_connection()?;
+    let mut pubsub = connection.as_pubsub();
+    pubsub.set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL_MS)))?;
+    channels.keys().try_for_each(|x| pubsub.subscribe(x))?;
+
+    for scrip in channels.values() {
+        if !send(fetch(&mut reader, scrip)?) {
+            return Ok(());
+        }
+    }
+    backoff.reset();
+
+    while !stop.is_set() {
+        let message = match pubsub.get_message() {
+            Ok(message) => message,
+            Err(e) if e.is_timeout() => continue,
+            Err(e) => return Err(e),
+        };
+        let payload: String = message.get_payload().unwrap_or_default();
+        if !source.is_update(&payload) {
+            continue;
+        }
+        if let Some(scrip) = channels.get(message.get_channel_name()) {
+            if !send(fetch(&mut reader, scrip)?) {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Updates through a std channel, for use from threads.
+pub fn subscribe(scrips: Vec<Scrip>, source: UpdateSource) -> (Subscription, std::sync::mpsc::Receiver<CompleteTicker>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let subscription = Subscription::new(scrips, source, move |x| sender.send(x).is_ok());
+    (subscription, receiver)
+}
+
+// Updates through a tokio channel holding up to `capacity` tickers, for use
+// from async code. Updates arriving while the channel is full are dropped;
+// the next update for the same scrip carries its latest state.
+pub fn subscribe_async(scrips: Vec<Scrip>, source: UpdateSource, capacity: usize) -> (Subscription, tokio::sync::mpsc::Receiver<CompleteTicker>) {
+    use tokio::sync::mpsc::error::TrySendError;
+    let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+    let subscription = Subscription::new(scrips, source, move |x| match sender.try_send(x) {
+        Ok(()) | Err(TrySendError::Full(_)) => true,
+        Err(TrySendError::Closed(_)) => false,
+    });
+    (subscription, receiver)
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Scrip;
+    use crate::stock::StockScrip;
+    use crate::subscription::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn channels() {
+        let scrip = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        assert_eq!(UpdateSource::Channel.channel(&scrip), "TEST:NSE:C:UPDATES");
+        assert_eq!(UpdateSource::Keyspace(0).channel(&scrip), "__keyspace@0__:TEST:NSE:C");
+        assert!(UpdateSource::Keyspace(0).is_update("hset"));
+        assert!(!UpdateSource::Keyspace(0).is_update("del"));
+        assert!(UpdateSource::Channel.is_update("400.23"));
+    }
+
+    fn connection_error() -> redis::RedisError {
+        redis::RedisError::from((redis::ErrorKind::IoError, "connection refused"))
+    }
+
+    #[test]
+    fn backoff_reset_on_connect() {
+        let stop: StopSignal = Default::default();
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+        let mut delays = Vec::new();
+        let mut attempts = 0;
+        retry(&stop, backoff, |backoff| {
+            attempts += 1;
+            delays.push(backoff.delay.as_millis());
+            match attempts {
+                // Connected, then lost the connection.
+                4 => { backoff.reset(); Err(connection_error()) },
+                6 => Ok(()),
+                _ => Err(connection_error()),
+            }
+        });
+        // Back to waiting 1ms after the fourth attempt.
+        assert_eq!(delays, vec![1, 2, 4, 4, 2, 4]);
+    }
+
+    #[test]
+    fn stop_wakes_backoff() {
+        let stop: Arc<StopSignal> = Default::default();
+        let stopper = stop.clone();
+        let started = std::time::Instant::now();
+        let handle = std::thread::spawn(move || {
+            let backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(60));
+            retry(&stopper, backoff, |_| Err(connection_error()));
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        stop.set();
+        handle.join().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,8 +1,8 @@
 use serde::{Serialize, Deserialize};
 use crate::scrip::Scrip;
-use crate::redis_utils::RedisScrip;
+use crate::redis_utils::{RedisScrip, fetch_tickers};
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OHLC {
     pub open: f64,
     pub high: f64,
@@ -11,13 +11,13 @@ pub struct OHLC {
     pub volume: u64,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DepthOrder {
     pub price: f64,
     pub quantity: u32,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Depth {
     pub depth: u8,
     pub total_bid: u32,
@@ -26,7 +26,7 @@ pub struct Depth {
     pub ask: Vec<DepthOrder>,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ticker {
     pub ltp: f64,
     pub ohlc: OHLC,
@@ -60,66 +60,80 @@ impl Ticker {
         self.depth = updated_ticker.depth;
     }
 
-    fn update_depth(&mut self, key: String, value: &redis::Value) {
+    fn update_depth(&mut self, key: String, value: &redis::Value) -> redis::RedisResult<()> {
         // For Depth -> Bid/Ask List
+        let unmapped = || redis::RedisError::from((redis::ErrorKind::TypeError, "Un-mapped ticker field", key.clone()));
         let parts: Vec<&str> = key.split(':').collect();
-        if parts.len() != 3 {
-            panic!("Un-mapped key -> {}", key);
+        let (bid_or_ask, rate_or_qty, val) = match parts[..] {
+            [bid_or_ask, rate_or_qty, val] => (bid_or_ask, rate_or_qty, val),
+            _ => return Err(unmapped()),
+        };
+
+        let idx = val.parse::<usize>().map_err(|_| unmapped())?;
+        let target: &mut Vec<DepthOrder> = match bid_or_ask {
+            "bid" => &mut self.depth.bid,
+            "ask" => &mut self.depth.ask,
+            _ => return Err(unmapped()),
+        };
+        if target.len() <= idx {
+            target.resize(idx + 1, Default::default());
         }
-
-        if let [bid_or_ask, rate_or_qty, val] = &parts[..3] {
-            let idx = val.parse::<usize>().unwrap();
-            let target: &mut Vec<DepthOrder> = match *bid_or_ask {
-                "bid" => &mut self.depth.bid,
-                "ask" => &mut self.depth.ask,
-                _ => panic!("Un-mapped key -> {}", key),
-            };
-            match target.get_mut(idx) {
-                Some(order) => match *rate_or_qty {
-                    "rate" => order.price = redis::from_redis_value(value).unwrap(),
-                    "quantity" => order.quantity = redis::from_redis_value(value).unwrap(),
-                    _ => panic!("Un-mapped key -> {}", key),
-                },
-                None => {
-                    target.resize(idx + 1, Default::default());
-                    match *rate_or_qty {
-                        "rate" => {
-                            target[idx].price = redis::from_redis_value(value).unwrap()
-                        }
-                        "quantity" => {
-                            target[idx].quantity = redis::from_redis_value(value).unwrap()
-                        }
-                        _ => panic!("Un-mapped key -> {}", key),
-                    }
-                }
-            }
+        match rate_or_qty {
+            "rate" => target[idx].price = redis::from_redis_value(value)?,
+            "quantity" => target[idx].quantity = redis::from_redis_value(value)?,
+            _ => return Err(unmapped()),
         }
+        Ok(())
     }
 
     // Expects a strict template of fields.
-    pub fn update(&mut self, key: String, value: &redis::Value) {
+    pub fn update(&mut self, key: String, value: &redis::Value) -> redis::RedisResult<()> {
         match key.as_str() {
-            "ltp" => self.ltp = redis::from_redis_value(value).unwrap(),
-            "open" => self.ohlc.open = redis::from_redis_value(value).unwrap(),
-            "high" => self.ohlc.high = redis::from_redis_value(value).unwrap(),
-            "low" => self.ohlc.low = redis::from_redis_value(value).unwrap(),
-            "close" => self.ohlc.close = redis::from_redis_value(value).unwrap(),
-            "depth" => self.depth.depth = redis::from_redis_value(value).unwrap(),
-            "total_bid" => self.depth.total_bid = redis::from_redis_value(value).unwrap(),
-            "total_ask" => self.depth.total_ask = redis::from_redis_value(value).unwrap(),
-            "total_volume" => self.ohlc.volume = redis::from_redis_value(value).unwrap(),
-            depth_key => self.update_depth(depth_key.to_string(), value),
+            "ltp" => self.ltp = redis::from_redis_value(value)?,
+            "open" => self.ohlc.open = redis::from_redis_value(value)?,
+            "high" => self.ohlc.high = redis::from_redis_value(value)?,
+            "low" => self.ohlc.low = redis::from_redis_value(value)?,
+            "close" => self.ohlc.close = redis::from_redis_value(value)?,
+            "depth" => self.depth.depth = redis::from_redis_value(value)?,
+            "total_bid" => self.depth.total_bid = redis::from_redis_value(value)?,
+            "total_ask" => self.depth.total_ask = redis::from_redis_value(value)?,
+            "total_volume" => self.ohlc.volume = redis::from_redis_value(value)?,
+            depth_key => self.update_depth(depth_key.to_string(), value)?,
         }
+        Ok(())
+    }
+
+    // Fields in the layout `update` expects, for writing the ticker hash.
+    pub fn to_fields(&self) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = vec![
+            (String::from("ltp"), self.ltp.to_string()),
+            (String::from("open"), self.ohlc.open.to_string()),
+            (String::from("high"), self.ohlc.high.to_string()),
+            (String::from("low"), self.ohlc.low.to_string()),
+            (String::from("close"), self.ohlc.close.to_string()),
+            (String::from("total_volume"), self.ohlc.volume.to_string()),
+            (String::from("depth"), self.depth.depth.to_string()),
+            (String::from("total_bid"), self.depth.total_bid.to_string()),
+            (String::from("total_ask"), self.depth.total_ask.to_string()),
+        ];
+        for (side, orders) in [("bid", &self.depth.bid), ("ask", &self.depth.ask)] {
+            orders.iter().enumerate().for_each(|(idx, order)| {
+                fields.push((format!("{}:rate:{}", side, idx), order.price.to_string()));
+                fields.push((format!("{}:quantity:{}", side, idx), order.quantity.to_string()));
+            });
+        }
+        fields
     }
 }
 
 impl redis::FromRedisValue for Ticker {
     fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
         let mut ticker: Ticker = Default::default();
-        v.as_map_iter()
-            .ok_or_else(|| panic!("Empty values found on Redis server"))
-            .unwrap_or_else(|_| panic!("Empty values found on Redis server"))
-            .for_each(|(k, v)| ticker.update(redis::from_redis_value(k).unwrap(), v));
+        let values = v.as_map_iter()
+            .ok_or_else(|| redis::RedisError::from((redis::ErrorKind::TypeError, "Ticker is not a hash")))?;
+        for (k, v) in values {
+            ticker.update(redis::from_redis_value(k)?, v)?;
+        }
         Ok(ticker)
     }
 }
@@ -136,9 +150,20 @@ impl CompleteTicker {
         Self { ticker, scrip }
     }
 
-    pub fn reload(&mut self) {
-        let updated_ticker = self.scrip.updated_ticker();
-        self.ticker.update_from_ticker(updated_ticker);
+    pub fn reload(&mut self) -> redis::RedisResult<()> {
+        Self::reload_all(std::slice::from_mut(self))
+    }
+
+    // Reloads every ticker in a single round trip.
+    pub fn reload_all(tickers: &mut [CompleteTicker]) -> redis::RedisResult<()> {
+        let scrips: Vec<Scrip> = tickers.iter().map(|x| x.scrip.clone()).collect();
+        let updated = fetch_tickers(&scrips)?;
+        tickers.iter_mut().for_each(|x| {
+            if let Some(ticker) = updated.get(&x.scrip).cloned() {
+                x.ticker.update_from_ticker(ticker);
+            }
+        });
+        Ok(())
     }
 }
 
@@ -150,3 +175,41 @@ impl std::fmt::Debug for CompleteTicker {
             .finish()
     }
 }
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::test_util::TEST_TICKER_1;
+
+    #[test]
+    fn reload_all() {
+        let scrip = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let stale = CompleteTicker { ticker: Ticker::new(), scrip };
+        // The same scrip twice gets the same ticker both times.
+        let mut tickers = vec![stale.clone(), stale.clone()];
+        CompleteTicker::reload_all(&mut tickers).unwrap();
+        assert!(tickers.iter().all(|x| x.ticker == *TEST_TICKER_1));
+
+        let mut single = stale;
+        single.reload().unwrap();
+        assert_eq!(single.ticker, *TEST_TICKER_1);
+    }
+
+    #[test]
+    fn bad_fields() {
+        use redis::Value;
+        let field = |k: &str, v: &str| vec![Value::Data(k.as_bytes().to_vec()), Value::Data(v.as_bytes().to_vec())];
+        let decode = |fields: Vec<Value>| redis::from_redis_value::<Ticker>(&Value::Bulk(fields));
+
+        let ticker = decode([field("ltp", "400.5"), field("bid:rate:1", "400.4")].concat()).unwrap();
+        assert_eq!(ticker.depth.bid[1].price, 400.4);
+        assert!(decode(field("ltp", "abc")).is_err());
+        assert!(decode(field("unknown", "1")).is_err());
+        assert!(decode(field("bid:rate:x", "1")).is_err());
+        assert!(decode(field("bid:price:0", "1")).is_err());
+    }
+}
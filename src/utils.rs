@@ -1,13 +1,17 @@
 use redis;
 use r2d2;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
 
 #[cfg(any(test, debug_assertions))]
 lazy_static::lazy_static! {
     pub static ref URL: &'static str = "redis://127.0.0.1";
     static ref PORT: u16 = 6379;
+    // Dedicated connections, e.g. for pub/sub, which cannot share the pool.
+    pub static ref CLIENT: redis::Client = redis::Client::open(format!("{}:{}", *URL, *PORT))
+        .unwrap();
     pub static ref POOL: r2d2::Pool<redis::Client> = r2d2::Pool::builder()
-        .build(redis::Client::open(format!("{}:{}", *URL, *PORT))
-               .unwrap())
+        .build(CLIENT.clone())
         .unwrap();
 }
 
@@ -15,8 +19,42 @@ lazy_static::lazy_static! {
 lazy_static::lazy_static! {
     static ref URL: &'static str = "redis://redis";
     static ref PORT: u16 = 6379;
+    // Dedicated connections, e.g. for pub/sub, which cannot share the pool.
+    pub static ref CLIENT: redis::Client = redis::Client::open(format!("{}:{}", *URL, *PORT))
+        .unwrap();
     pub static ref POOL: r2d2::Pool<redis::Client> = r2d2::Pool::builder()
-        .build(redis::Client::open(format!("{}:{}", *URL, *PORT))
-               .unwrap())
+        .build(CLIENT.clone())
         .unwrap();
 }
+
+// Pooled connection, with a pool timeout reported as a redis error.
+pub(crate) fn pooled_connection() -> redis::RedisResult<r2d2::PooledConnection<redis::Client>> {
+    POOL.clone().get().map_err(|e| {
+        redis::RedisError::from((redis::ErrorKind::IoError, "Unable to get a connection", e.to_string()))
+    })
+}
+
+// Stop flag for background loops that also wakes them up from a wait.
+#[derive(Default)]
+pub struct StopSignal {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl StopSignal {
+    pub fn is_set(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    pub fn set(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.wake.notify_all();
+    }
+
+    // Sleeps for `timeout` or until set, returning whether it was set.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap();
+        let (stopped, _) = self.wake.wait_timeout_while(stopped, timeout, |x| !*x).unwrap();
+        *stopped
+    }
+}
@@ -0,0 +1,339 @@
+use crate::scrip::Scrip;
+use crate::position::Position;
+use crate::redis_utils::RedisScrip;
+use crate::pricing::{BlackScholes, norm_cdf, norm_pdf};
+use chrono::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+// =============================================================================
+//                               Holding Repricing
+// =============================================================================
+
+// A holding and how it is revalued when its underlying moves.
+struct Revaluation {
+    scrip: Scrip,
+    quantity: f64,
+    // Scrip whose price move drives the holding.
+    driver: Scrip,
+    spot: f64,
+    // `None` for stocks and indices, which move one to one with the driver.
+    model: Option<BlackScholes>,
+}
+
+impl Revaluation {
+    // P&L when the driver moves by `spot_return` (fraction), implied volatility
+    // moves by `iv_shift` points and `days` pass.
+    fn pnl(&self, spot_return: f64, iv_shift: f64, days: f64) -> f64 {
+        match &self.model {
+            None => self.quantity * self.spot * spot_return,
+            Some(model) => {
+                let shocked = BlackScholes {
+                    spot: model.spot * (1.0 + spot_return),
+                    volatility: (model.volatility + iv_shift / 100.0).max(0.0),
+                    time: (model.time - days / 365.0).max(0.0),
+                    ..model.clone()
+                };
+                self.quantity * (shocked.price() - model.price())
+            }
+        }
+    }
+}
+
+impl Position {
+    // Holdings that can be repriced, and the ones that cannot (options without
+    // an underlying or a valid implied volatility).
+    fn revaluations(&self, rate: f64, now: DateTime<Local>) -> (Vec<Revaluation>, Vec<Scrip>) {
+        let mut unpriced: Vec<Scrip> = Vec::new();
+        let revaluations = self.holding
+            .iter()
+            .filter_map(|(scrip, (quantity, _))| {
+                let quantity = *quantity as f64;
+                let revaluation = match scrip {
+                    Scrip::Stock(_) | Scrip::Index(_) => Some(Revaluation {
+                        scrip: scrip.clone(),
+                        quantity,
+                        driver: scrip.clone(),
+                        spot: scrip.updated_ticker().ltp,
+                        model: None,
+                    }),
+                    Scrip::Option(o) => o.pricer(rate, now).zip(o.underlying.clone()).map(|(model, driver)| {
+                        Revaluation {
+                            scrip: scrip.clone(),
+                            quantity,
+                            driver: *driver,
+                            spot: model.spot,
+                            model: Some(model),
+                        }
+                    }),
+                };
+                if revaluation.is_none() {
+                    unpriced.push(scrip.clone());
+                }
+                revaluation
+            })
+            .collect();
+        (revaluations, unpriced)
+    }
+}
+
+// =============================================================================
+//                               Value at Risk
+// =============================================================================
+
+// Losses are reported as positive numbers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VarReport {
+    pub confidence: f64,
+    pub var: f64,
+    pub expected_shortfall: f64,
+    pub observations: usize,
+    // Holdings left out of the P&Ls because they could not be priced.
+    pub unpriced: Vec<Scrip>,
+}
+
+// Close to close returns from the stored candles, keyed by candle timestamp.
+pub fn candle_returns(scrip: &Scrip) -> BTreeMap<DateTime<Utc>, f64> {
+    let closes: Vec<(DateTime<Utc>, f64)> = scrip
+        .candle_ts()
+        .into_iter()
+        .filter_map(|ts| scrip.candle_from_timestamp(ts).map(|c| (ts, c.close)))
+        .collect();
+
+    closes
+        .windows(2)
+        .filter(|x| x[0].1 != 0.0)
+        .map(|x| (x[1].0, x[1].1 / x[0].1 - 1.0))
+        .collect()
+}
+
+// P&Ls that are NaN or infinite, e.g. from a missing price, are left out.
+fn finite(pnls: &[f64]) -> Vec<f64> {
+    pnls.iter().copied().filter(|x| x.is_finite()).collect()
+}
+
+// `None` unless the confidence is strictly between 0 and 1.
+pub fn historical_var(pnls: &[f64], confidence: f64) -> Option<VarReport> {
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return None;
+    }
+    let mut sorted = finite(pnls);
+    if sorted.is_empty() {
+        return Some(VarReport { confidence, ..Default::default() });
+    }
+
+    sorted.sort_by(|x, y| x.total_cmp(y));
+    let tail = (((1.0 - confidence) * sorted.len() as f64).floor() as usize).max(1);
+    let tail_losses = &sorted[..tail];
+
+    Some(VarReport {
+        confidence,
+        var: -tail_losses[tail - 1],
+        expected_shortfall: -tail_losses.iter().sum::<f64>() / tail as f64,
+        observations: sorted.len(),
+        ..Default::default()
+    })
+}
+
+// Normal approximation of the P&L distribution.
+pub fn parametric_var(pnls: &[f64], confidence: f64) -> Option<VarReport> {
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return None;
+    }
+    let pnls = finite(pnls);
+    if pnls.len() < 2 {
+        return Some(VarReport { confidence, observations: pnls.len(), ..Default::default() });
+    }
+
+    let n = pnls.len() as f64;
+    let mean = pnls.iter().sum::<f64>() / n;
+    let std = (pnls.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    let z = inverse_norm_cdf(confidence);
+
+    Some(VarReport {
+        confidence,
+        var: z * std - mean,
+        expected_shortfall: std * norm_pdf(z) / (1.0 - confidence) - mean,
+        observations: pnls.len(),
+        ..Default::default()
+    })
+}
+
+fn inverse_norm_cdf(p: f64) -> f64 {
+    let (mut low, mut high) = (-10.0, 10.0);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if norm_cdf(mid) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+impl Position {
+    // P&L of the current holdings under every historical move of their
+    // underlyings over `horizon` candle periods. Only timestamps present in
+    // every underlying's history are used. One period returns are scaled by
+    // sqrt(horizon), which assumes they are independent. Holdings that could
+    // not be priced are returned alongside.
+    pub fn historical_pnls(&self, horizon: f64, rate: f64, now: DateTime<Local>) -> (Vec<f64>, Vec<Scrip>) {
+        let (revaluations, unpriced) = self.revaluations(rate, now);
+        let mut returns: HashMap<Scrip, BTreeMap<DateTime<Utc>, f64>> = HashMap::new();
+        revaluations.iter().for_each(|x| {
+            returns.entry(x.driver.clone()).or_insert_with(|| candle_returns(&x.driver));
+        });
+
+        let timestamps: Vec<DateTime<Utc>> = match returns.values().next() {
+            Some(first) => first
+                .keys()
+                .filter(|ts| returns.values().all(|r| r.contains_key(ts)))
+                .copied()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let scale = horizon.max(0.0).sqrt();
+        let pnls = timestamps
+            .iter()
+            .map(|ts| {
+                revaluations
+                    .iter()
+                    .map(|x| x.pnl(returns[&x.driver][ts] * scale, 0.0, 0.0))
+                    .sum()
+            })
+            .collect();
+        (pnls, unpriced)
+    }
+
+    pub fn historical_var(&self, confidence: f64, horizon: f64, rate: f64, now: DateTime<Local>) -> Option<VarReport> {
+        let (pnls, unpriced) = self.historical_pnls(horizon, rate, now);
+        historical_var(&pnls, confidence).map(|x| VarReport { unpriced, ..x })
+    }
+
+    pub fn parametric_var(&self, confidence: f64, horizon: f64, rate: f64, now: DateTime<Local>) -> Option<VarReport> {
+        let (pnls, unpriced) = self.historical_pnls(horizon, rate, now);
+        parametric_var(&pnls, confidence).map(|x| VarReport { unpriced, ..x })
+    }
+}
+
+// =============================================================================
+//                              Stress Scenarios
+// =============================================================================
+
+#[derive(Clone, Debug, Default)]
+pub struct Scenario {
+    pub name: String,
+    // Move of every underlying as a fraction, -0.05 for a 5% fall.
+    pub underlying_shift: f64,
+    // Change in implied volatility in points, 10.0 for IV +10.
+    pub iv_shift: f64,
+    // Calendar days to move forward, for time decay.
+    pub days: f64,
+}
+
+impl Scenario {
+    pub fn new(name: &str, underlying_shift: f64, iv_shift: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            underlying_shift,
+            iv_shift,
+            days: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub pnl: f64,
+    // Keyed by the scrip's key.
+    pub per_scrip: HashMap<String, f64>,
+    // Holdings left out of the P&L because they could not be priced.
+    pub unpriced: Vec<Scrip>,
+}
+
+impl Position {
+    pub fn stress_test(&self, scenarios: &[Scenario], rate: f64, now: DateTime<Local>) -> Vec<ScenarioResult> {
+        let (revaluations, unpriced) = self.revaluations(rate, now);
+        scenarios
+            .iter()
+            .map(|scenario| {
+                let per_scrip: HashMap<String, f64> = revaluations
+                    .iter()
+                    .map(|x| {
+                        let pnl = x.pnl(scenario.underlying_shift, scenario.iv_shift, scenario.days);
+                        (x.scrip.key(), pnl)
+                    })
+                    .collect();
+                ScenarioResult {
+                    name: scenario.name.clone(),
+                    pnl: per_scrip.values().sum(),
+                    per_scrip,
+                    unpriced: unpriced.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use crate::var::*;
+    use chrono::Local;
+
+    #[test]
+    fn historical_tail() {
+        let pnls: Vec<f64> = (1..=100).map(|x| x as f64 - 50.0).collect();
+        let report = historical_var(&pnls, 0.95).unwrap();
+        assert_eq!(report.var, 45.0);
+        assert_eq!(report.expected_shortfall, 47.0);
+    }
+
+    #[test]
+    fn non_finite_and_bad_confidence() {
+        let mut pnls: Vec<f64> = (1..=100).map(|x| x as f64 - 50.0).collect();
+        pnls.push(f64::NAN);
+        pnls.push(f64::NEG_INFINITY);
+        let report = historical_var(&pnls, 0.95).unwrap();
+        assert_eq!(report.var, 45.0);
+        assert_eq!(report.observations, 100);
+        assert!(parametric_var(&pnls, 0.95).unwrap().var.is_finite());
+
+        assert!(historical_var(&pnls, 1.0).is_none());
+        assert!(parametric_var(&pnls, 1.0).is_none());
+        assert!(parametric_var(&pnls, 0.0).is_none());
+    }
+
+    #[test]
+    fn parametric_zero_mean() {
+        let report = parametric_var(&[-1.0, 1.0, -1.0, 1.0], 0.95).unwrap();
+        let std = (4.0f64 / 3.0).sqrt();
+        assert!((report.var - 1.6449 * std).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stock_stress() {
+        let mut position: Position = Default::default();
+        position.update_holding(Scrip::Stock(StockScrip::new("TEST", "NSE", "C")), 10, 400.0);
+        let results = position.stress_test(&[Scenario::new("Down 5%", -0.05, 0.0)], 0.05, Local::now());
+        assert!((results[0].pnl + 10.0 * 400.23 * 0.05).abs() < 1e-9);
+        assert!(results[0].unpriced.is_empty());
+    }
+
+    #[test]
+    fn unpriced_option_reported() {
+        let expiry = chrono::NaiveDate::from_ymd_opt(2022, 6, 30).unwrap();
+        let option = Scrip::Option(OptionScrip::new("TEST", "NSE", "O", expiry, 400, OptionType::CE, None));
+        let mut position: Position = Default::default();
+        position.update_holding(option.clone(), 10, 5.0);
+        let results = position.stress_test(&[Scenario::new("Down 5%", -0.05, 0.0)], 0.05, Local::now());
+        assert_eq!(results[0].pnl, 0.0);
+        assert_eq!(results[0].unpriced, vec![option]);
+    }
+}
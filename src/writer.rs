@@ -0,0 +1,114 @@
+use crate::tickers::Ticker;
+use crate::redis_utils::RedisScrip;
+use crate::utils::pooled_connection;
+
+// =============================================================================
+//                                Ticker Writer
+// =============================================================================
+
+// Writes tickers in the hash layout that `Ticker::update` reads, for feed
+// handlers. The hash is replaced atomically so that levels missing from a
+// shallower depth do not linger. The DEL and HSET each raise a keyspace
+// event; `UpdateSource::Keyspace` subscribers only react to the `hset`.
+#[derive(Clone, Debug, Default)]
+pub struct TickerWriter {
+    // PUBLISH the `ltp` on the scrip's `updates_channel` after each write.
+    pub publish: bool,
+    // XADD `ltp` and `total_volume` to the scrip's `ticks_key`.
+    pub stream: bool,
+    // Approximate length the tick stream is trimmed to.
+    pub stream_max_len: Option<usize>,
+}
+
+impl TickerWriter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_publish(mut self) -> Self {
+        self.publish = true;
+        self
+    }
+
+    pub fn with_stream(mut self, max_len: Option<usize>) -> Self {
+        self.stream = true;
+        self.stream_max_len = max_len;
+        self
+    }
+
+    // Adds the commands for one ticker to `pipeline`.
+    pub fn add_to_pipeline(&self, pipeline: &mut redis::Pipeline, scrip: &dyn RedisScrip, ticker: &Ticker) {
+        let key = scrip.key();
+        pipeline
+            .del(&key).ignore()
+            .hset_multiple(&key, &ticker.to_fields()).ignore();
+
+        if self.publish {
+            pipeline.publish(scrip.updates_channel(), ticker.ltp).ignore();
+        }
+        if self.stream {
+            let cmd = pipeline.cmd("XADD").arg(scrip.ticks_key());
+            if let Some(max_len) = self.stream_max_len {
+                cmd.arg("MAXLEN").arg("~").arg(max_len);
+            }
+            cmd.arg("*")
+                .arg("ltp").arg(ticker.ltp)
+                .arg("total_volume").arg(ticker.ohlc.volume)
+                .ignore();
+        }
+    }
+
+    pub fn pipeline(&self, tickers: &[(&dyn RedisScrip, &Ticker)]) -> redis::Pipeline {
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        tickers.iter().for_each(|(scrip, ticker)| self.add_to_pipeline(&mut pipeline, *scrip, ticker));
+        pipeline
+    }
+
+    pub fn write(&self, scrip: &dyn RedisScrip, ticker: &Ticker) -> redis::RedisResult<()> {
+        self.write_many(&[(scrip, ticker)])
+    }
+
+    // All tickers in a single round trip.
+    pub fn write_many(&self, tickers: &[(&dyn RedisScrip, &Ticker)]) -> redis::RedisResult<()> {
+        let mut connection = pooled_connection()?;
+        self.pipeline(tickers).query(&mut *connection)
+    }
+}
+
+// =============================================================================
+//                                  Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::scrip::Scrip;
+    use crate::stock::StockScrip;
+    use crate::tickers::Ticker;
+    use crate::test_util::TEST_TICKER_1;
+    use crate::writer::*;
+
+    #[test]
+    fn fields_roundtrip() {
+        let values: Vec<redis::Value> = TEST_TICKER_1
+            .to_fields()
+            .into_iter()
+            .flat_map(|(k, v)| [redis::Value::Data(k.into_bytes()), redis::Value::Data(v.into_bytes())])
+            .collect();
+        let ticker: Ticker = redis::from_redis_value(&redis::Value::Bulk(values)).unwrap();
+        assert_eq!(ticker, *TEST_TICKER_1);
+    }
+
+    #[test]
+    fn writer_commands() {
+        let scrip = Scrip::Stock(StockScrip::new("TEST", "NSE", "C"));
+        let writer = TickerWriter::new().with_publish().with_stream(Some(1000));
+        let pipeline = writer.pipeline(&[(&scrip, &*TEST_TICKER_1)]);
+        let packed = String::from_utf8(pipeline.get_packed_pipeline()).unwrap();
+
+        assert!(packed.contains("TEST:NSE:C:UPDATES"));
+        assert!(packed.contains("TEST:NSE:C:TICKS"));
+        assert!(packed.contains("ask:quantity:4"));
+        assert!(packed.contains("MULTI"));
+    }
+}